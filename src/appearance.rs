@@ -0,0 +1,231 @@
+//! Generation of the content of appearance streams for fields holding text
+
+use metrics::text_width;
+use Color;
+use Quadding;
+
+/// The space left between the edges of the widget and its text
+const PADDING: f64 = 2.0;
+/// The largest font size used when the default appearance asks for auto sizing (a size of 0)
+const MAX_AUTO_FONT_SIZE: f64 = 12.0;
+/// The smallest font size auto sizing shrinks text to
+const MIN_AUTO_FONT_SIZE: f64 = 4.0;
+/// The distance between the baselines of lines of multiline text, as a multiple of font size
+const LEADING: f64 = 1.15;
+
+/// The parts of a default appearance ("DA") string, e.g. `/Helv 12 Tf 0 g`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefaultAppearance {
+    /// The name of the font in the form's resources
+    pub font: String,
+    /// The font size, where 0 means the text is sized to fit the field
+    pub size: f64,
+    /// The text color, if the string sets one
+    pub color: Option<Color>,
+}
+
+impl DefaultAppearance {
+    /// Parses a default appearance string. Returns `None` if it doesn't select a font.
+    pub fn parse(da: &str) -> Option<DefaultAppearance> {
+        let tokens: Vec<&str> = da.split_whitespace().collect();
+        let mut font = None;
+        let mut color = None;
+        for (i, &token) in tokens.iter().enumerate() {
+            let operands = match token {
+                "Tf" => 2,
+                "g" => 1,
+                "rg" => 3,
+                "k" => 4,
+                _ => continue,
+            };
+            if i < operands {
+                continue;
+            }
+            if token == "Tf" {
+                let name = tokens[i - 2].trim_start_matches('/').to_owned();
+                let size = tokens[i - 1].parse().unwrap_or(0.0);
+                font = Some((name, size));
+            } else {
+                let values: Vec<f64> = tokens[i - operands..i]
+                    .iter()
+                    .map(|value| value.parse().unwrap_or(0.0))
+                    .collect();
+                color = Some(match values.len() {
+                    1 => Color::Gray(values[0]),
+                    3 => Color::Rgb(values[0], values[1], values[2]),
+                    _ => Color::Cmyk(values[0], values[1], values[2], values[3]),
+                });
+            }
+        }
+        font.map(|(font, size)| DefaultAppearance { font, size, color })
+    }
+}
+
+/// Replaces the color set by a default appearance string with `color`, keeping the rest of it
+pub fn replace_color(da: &str, color: Color) -> String {
+    let tokens: Vec<&str> = da.split_whitespace().collect();
+    let mut kept: Vec<&str> = Vec::new();
+    for &token in &tokens {
+        let operands = match token {
+            "g" => 1,
+            "rg" => 3,
+            "k" => 4,
+            _ => {
+                kept.push(token);
+                continue;
+            }
+        };
+        let len = kept.len();
+        kept.truncate(len.saturating_sub(operands));
+    }
+    let mut da = kept.join(" ");
+    if !da.is_empty() {
+        da.push(' ');
+    }
+    da.push_str(&color_operator(color));
+    da
+}
+
+/// Formats the operands and operator setting `color` as the fill color, e.g. `0 g`
+fn color_operator(color: Color) -> String {
+    match color {
+        Color::Gray(gray) => format!("{} g", number(gray)),
+        Color::Rgb(r, g, b) => format!("{} {} {} rg", number(r), number(g), number(b)),
+        Color::Cmyk(c, m, y, k) => {
+            format!("{} {} {} {} k", number(c), number(m), number(y), number(k))
+        }
+    }
+}
+
+/// Builds the content of a normal appearance stream showing `text` in a widget of the given
+/// size. `base_font` is the name of the standard font used to measure the text. Multiline text is
+/// wrapped to the width of the widget and laid out from the top, while other text is shown on a
+/// single line centered vertically.
+pub fn text_content(
+    da: &DefaultAppearance,
+    base_font: &str,
+    text: &str,
+    width: f64,
+    height: f64,
+    quadding: Quadding,
+    multiline: bool,
+) -> Vec<u8> {
+    let size = if da.size > 0.0 {
+        da.size
+    } else if multiline {
+        MAX_AUTO_FONT_SIZE
+    } else {
+        auto_font_size(base_font, text, width, height)
+    };
+    let lines = if multiline {
+        wrap(base_font, size, text, width - 2.0 * PADDING)
+    } else {
+        vec![text.to_owned()]
+    };
+    let y = if multiline {
+        // The first baseline sits one ascent below the top padding
+        height - PADDING - 0.78 * size
+    } else {
+        // Center the line vertically, allowing for the part of the font below the baseline
+        (height - size) / 2.0 + 0.22 * size
+    };
+
+    let mut content = Vec::new();
+    content.extend_from_slice(b"/Tx BMC\nq\nBT\n");
+    content.extend_from_slice(format!("/{} {} Tf\n", da.font, number(size)).as_bytes());
+    if let Some(color) = da.color {
+        content.extend_from_slice(color_operator(color).as_bytes());
+        content.push(b'\n');
+    }
+    // Each line is positioned relative to the start of the previous one
+    let (mut last_x, mut last_y) = (0.0, 0.0);
+    for (i, line) in lines.iter().enumerate() {
+        let line_width = text_width(base_font, size, line);
+        let x = match quadding {
+            Quadding::Left => PADDING,
+            Quadding::Center => (width - line_width) / 2.0,
+            Quadding::Right => width - PADDING - line_width,
+        };
+        let line_y = y - i as f64 * size * LEADING;
+        content.extend_from_slice(
+            format!("{} {} Td\n", number(x - last_x), number(line_y - last_y)).as_bytes(),
+        );
+        content.extend_from_slice(&string_literal(line));
+        content.extend_from_slice(b" Tj\n");
+        last_x = x;
+        last_y = line_y;
+    }
+    content.extend_from_slice(b"ET\nQ\nEMC\n");
+    content
+}
+
+/// Breaks text into lines no wider than `max_width`, breaking between words where possible and
+/// always at newlines
+fn wrap(base_font: &str, size: f64, text: &str, max_width: f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_owned()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if text_width(base_font, size, &candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            // A word too long for a line of its own is broken between characters
+            line = String::new();
+            for c in word.chars() {
+                line.push(c);
+                if text_width(base_font, size, &line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(line);
+                    line = c.to_string();
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Picks the largest font size at which the text fits in the widget
+fn auto_font_size(base_font: &str, text: &str, width: f64, height: f64) -> f64 {
+    let mut size = MAX_AUTO_FONT_SIZE.min(height - 2.0 * PADDING);
+    let unit_width = text_width(base_font, 1.0, text);
+    if unit_width > 0.0 {
+        size = size.min((width - 2.0 * PADDING) / unit_width);
+    }
+    size.max(MIN_AUTO_FONT_SIZE)
+}
+
+/// Formats a number for a content stream, without needless decimals
+fn number(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded.fract() == 0.0 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+/// Writes text as a PDF string literal in the font's single byte encoding, escaping the
+/// characters that have a special meaning
+fn string_literal(text: &str) -> Vec<u8> {
+    let mut literal = vec![b'('];
+    for c in text.chars() {
+        let byte = if (c as u32) < 0x100 { c as u8 } else { b'?' };
+        if byte == b'(' || byte == b')' || byte == b'\\' {
+            literal.push(b'\\');
+        }
+        literal.push(byte);
+    }
+    literal.push(b')');
+    literal
+}
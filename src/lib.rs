@@ -2,15 +2,27 @@
 extern crate lopdf;
 #[macro_use]
 extern crate bitflags;
-#[macro_use]
-extern crate derive_error;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+
+mod appearance;
+mod metrics;
+mod scripts;
+mod writer;
 
-use lopdf::{Document, Object, ObjectId, StringFormat};
-use std::collections::VecDeque;
+use appearance::DefaultAppearance;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 bitflags! {
     struct ButtonFlags: u32 {
@@ -33,18 +45,42 @@ bitflags! {
     }
 }
 
+bitflags! {
+    struct TextFlags: u32 {
+        const MULTILINE         = 0x1000;
+        const PASSWORD          = 0x2000;
+        const FILE_SELECT       = 0x100000;
+        const DO_NOT_SPELLCHECK = 0x400000;
+        const DO_NOT_SCROLL     = 0x800000;
+        const COMB              = 0x1000000;
+        const RICH_TEXT         = 0x2000000;
+    }
+}
+
 /// A PDF Form that contains fillable fields
 ///
 /// Use this struct to load an existing PDF with a fillable form using the `load` method.  It will
 /// analyze the PDF and identify the fields. Then you can get and set the content of the fields by
 /// index.
+///
+/// Documents that keep their objects in compressed object streams (PDF 1.5 and later) are
+/// supported. The streams are expanded while the document is parsed, so fields stored in them are
+/// found like any other.
 pub struct Form {
     doc: Document,
     form_ids: Vec<ObjectId>,
+    track_field_modifications: bool,
+    clear_xfa_on_change: bool,
+    regenerate_id_on_save: bool,
+    preserve_appearance_font: bool,
+    embed_values_on_save: bool,
+    trim_text: bool,
+    /// The length of the file the form was loaded from, without trailing whitespace
+    source_len: Option<usize>,
 }
 
 /// The possible types of fillable form fields in a PDF
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FieldType {
     Button,
     Radio,
@@ -67,39 +103,201 @@ pub enum FieldState {
     /// The toggle state of the checkbox
     CheckBox { is_checked: bool },
     /// `selected` is the list of selected options from `options`
+    ///
+    /// `commit_on_change` is true if the value is committed as soon as the selection changes,
+    /// rather than when the field loses focus
     ListBox {
         selected: Vec<String>,
         options: Vec<String>,
         multiselect: bool,
+        commit_on_change: bool,
     },
     /// `selected` is the list of selected options from `options`
+    ///
+    /// `commit_on_change` is true if the value is committed as soon as the selection changes,
+    /// rather than when the field loses focus
     ComboBox {
         selected: Vec<String>,
         options: Vec<String>,
         editable: bool,
+        commit_on_change: bool,
     },
     /// User Text Input
     Text { text: String },
 }
 
-#[derive(Debug, Error)]
+/// A value to fill a form field with, by type of field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// The text of a text field
+    Text(String),
+    /// Whether a check box is checked
+    CheckBox(bool),
+    /// The option of a radio button group to select
+    Radio(String),
+    /// The options of a list box to select
+    ListBox(Vec<String>),
+    /// The option of a combo box to select, or custom text if it is editable
+    ComboBox(String),
+}
+
+/// An action performed when a button is clicked, or on other events such as opening the document
+#[derive(Debug, Clone, PartialEq)]
+pub enum ButtonAction {
+    /// Runs the JavaScript
+    JavaScript(String),
+    /// Opens the URI
+    Uri(String),
+    /// Runs a viewer command, such as "Print" or "NextPage"
+    Named(String),
+    /// Submits the form's data to the URL
+    SubmitForm(String),
+    /// Resets fields to their default values
+    ResetForm,
+    /// Goes to a destination in the document
+    GoTo,
+    /// Any other kind of action, by its "S" type
+    Other(String),
+}
+
+/// The appearances a widget may define in its "AP" dictionary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppearanceVariant {
+    /// The appearance used when the widget is not being interacted with
+    Normal,
+    /// The appearance used while the mouse button is pressed over the widget
+    Down,
+    /// The appearance used while the cursor hovers over the widget
+    Rollover,
+}
+
+/// The justification of a field's text ("Q")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quadding {
+    Left,
+    Center,
+    Right,
+}
+
+/// A color in one of the device color spaces, with components from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Gray(f64),
+    Rgb(f64, f64, f64),
+    Cmyk(f64, f64, f64, f64),
+}
+
+/// The background of a widget, from the "BG" entry of its appearance characteristics ("MK")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidgetBackground {
+    /// A flat color
+    Color(Color),
+    /// A pattern or shading rather than a flat color. The standard only allows colors here, but
+    /// some producers refer to a pattern or shading instead.
+    Pattern,
+}
+
+/// The text settings of a field's default appearance ("DA")
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppearanceSettings {
+    /// The name of the font in the form's resources, e.g. "Helv"
+    pub font: String,
+    /// The font size, where 0 means the text is sized to fit the field
+    pub font_size: f64,
+    /// The text color, if one is set
+    pub color: Option<Color>,
+}
+
+/// How viewers should present the document, from the catalog's "ViewerPreferences". Each is
+/// false when not set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViewerPreferences {
+    /// Hide the viewer's tool bars while the document is active ("HideToolbar")
+    pub hide_toolbar: bool,
+    /// Hide the viewer's menu bar while the document is active ("HideMenubar")
+    pub hide_menubar: bool,
+    /// Show the document's title ("Title" in its information dictionary) in the window's title
+    /// bar instead of the file name ("DisplayDocTitle")
+    pub display_doc_title: bool,
+}
+
+/// Bounds on the documents `Form::load_with_limits` accepts. The default sets no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most field dictionaries read from the field tree, counting the non-terminal fields
+    /// that group others
+    pub max_fields: usize,
+    /// The deepest nesting of fields in "Kids", where top level fields have a depth of 1
+    pub max_depth: usize,
+    /// The largest document, in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_fields: usize::MAX,
+            max_depth: usize::MAX,
+            max_bytes: usize::MAX,
+        }
+    }
+}
+
+/// A structural problem found in a form by `Form::audit`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormIssue {
+    /// The index of the field the problem was found in
+    pub field: usize,
+    /// The full name of the field, if it has one
+    pub name: Option<String>,
+    /// What is wrong
+    pub description: String,
+}
+
+/// How a document's form is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormKind {
+    /// Only AcroForm fields
+    AcroForm,
+    /// An XFA form, with no AcroForm fields to fill in
+    XfaOnly,
+    /// Both AcroForm fields and an XFA form. Viewers that support XFA show the XFA form, so
+    /// changes to the AcroForm fields may not be displayed
+    Hybrid,
+}
+
+/// How a string value is encoded. See `Form::value_encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringEncoding {
+    /// PDFDocEncoding, which matches Latin-1 for printable characters, in a literal string
+    PdfDocEncoding,
+    /// UTF-16BE, marked by a byte order mark
+    Utf16Be,
+    /// PDFDocEncoding in a hexadecimal string
+    Hex,
+}
+
+#[derive(Debug)]
 /// Errors that may occur while loading a PDF
 pub enum LoadError {
     /// An IO Error
     IoError(io::Error),
+    /// The PDF itself could not be parsed
+    PdfError(lopdf::Error),
     /// A dictionary key that must be present in order to find forms was not present
     DictionaryKeyNotFound,
     /// The reference `ObjectId` did not point to any values
-    #[error(non_std, no_from)]
     NoSuchReference(ObjectId),
     /// An element that was expected to be a reference was not a reference
     NotAReference,
     /// A value that must be a certain type was not that type
     UnexpectedType,
+    /// The document exceeds the limits it was loaded with. See `Form::load_with_limits`.
+    LimitExceeded,
 }
 
 /// Errors That may occur while setting values in a form
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub enum ValueError {
     /// The method used to set the state is incompatible with the type of the field
     TypeMismatch,
@@ -107,10 +305,86 @@ pub enum ValueError {
     InvalidSelection,
     /// Multiple values were selected when only one was allowed
     TooManySelected,
+    /// The text is longer than the field's maximum length
+    TextTooLong,
+    /// The page index does not exist in the document
+    InvalidPage,
+    /// The field has no usable default appearance ("DA") to generate its appearance from
+    MissingDefaultAppearance,
+    /// No field has the given name
+    NoSuchField,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::IoError(ref err) => write!(f, "IO error: {}", err),
+            LoadError::PdfError(ref err) => write!(f, "could not parse PDF: {}", err),
+            LoadError::DictionaryKeyNotFound => {
+                write!(
+                    f,
+                    "a dictionary key needed to find the form was not present"
+                )
+            }
+            LoadError::NoSuchReference((id, generation)) => {
+                write!(
+                    f,
+                    "reference {} {} R does not point to any object",
+                    id, generation
+                )
+            }
+            LoadError::NotAReference => write!(f, "expected a reference"),
+            LoadError::UnexpectedType => write!(f, "an object was not of the expected type"),
+            LoadError::LimitExceeded => write!(f, "the document exceeds the load limits"),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            LoadError::IoError(ref err) => Some(err),
+            LoadError::PdfError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::IoError(err)
+    }
+}
+
+impl From<lopdf::Error> for LoadError {
+    fn from(err: lopdf::Error) -> Self {
+        LoadError::PdfError(err)
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match *self {
+            ValueError::TypeMismatch => "the field is not of the type the value was set as",
+            ValueError::InvalidSelection => "one or more selected values are not valid choices",
+            ValueError::TooManySelected => "multiple values were selected but only one is allowed",
+            ValueError::TextTooLong => "the text is longer than the field's maximum length",
+            ValueError::InvalidPage => "the page does not exist in the document",
+            ValueError::MissingDefaultAppearance => {
+                "the field has no usable default appearance to generate its appearance from"
+            }
+            ValueError::NoSuchField => "no field has the given name",
+        };
+        f.write_str(message)
+    }
 }
 
+impl error::Error for ValueError {}
+
 trait PdfObjectDeref {
     fn deref<'a>(&self, doc: &'a Document) -> Result<&'a Object, LoadError>;
+    /// Follows the object if it is a reference, otherwise returns the object itself
+    fn resolve<'a>(&'a self, doc: &'a Document) -> &'a Object;
 }
 
 impl PdfObjectDeref for Object {
@@ -120,59 +394,575 @@ impl PdfObjectDeref for Object {
             _ => Err(LoadError::NotAReference),
         }
     }
+
+    fn resolve<'a>(&'a self, doc: &'a Document) -> &'a Object {
+        match *self {
+            Object::Reference(oid) => doc.objects.get(&oid).unwrap_or(self),
+            _ => self,
+        }
+    }
+}
+
+/// Decodes a PDF text string, which is either UTF-16BE with a byte order mark or PDFDocEncoding
+fn decode_text(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| (u16::from(pair[0]) << 8) | u16::from(pair[1]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        // PDFDocEncoding matches Latin-1 for every printable character forms commonly contain
+        bytes.iter().map(|&b| char::from(b)).collect()
+    }
+}
+
+/// Encodes a string as a PDF text string, using UTF-16BE only when Latin-1 cannot represent it
+fn encode_text(s: &str) -> Vec<u8> {
+    if s.chars().all(|c| (c as u32) < 0x100) {
+        s.chars().map(|c| c as u8).collect()
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in s.encode_utf16() {
+            bytes.push((unit >> 8) as u8);
+            bytes.push(unit as u8);
+        }
+        bytes
+    }
+}
+
+/// Re-encodes the strings in a value with `encode_text`, so that they are Latin-1 literals when
+/// they can be and UTF-16BE otherwise. Strings without a byte order mark that are valid UTF-8,
+/// and not plain ASCII, are taken to have been written as UTF-8 by mistake.
+fn normalize_strings(value: &mut Object) {
+    match *value {
+        Object::String(ref mut bytes, ref mut format) => {
+            let text = if bytes.starts_with(&[0xFE, 0xFF]) || bytes.is_ascii() {
+                decode_text(bytes)
+            } else {
+                match str::from_utf8(bytes) {
+                    Ok(text) => text.to_owned(),
+                    Err(_) => decode_text(bytes),
+                }
+            };
+            *bytes = encode_text(&text);
+            *format = StringFormat::Literal;
+        }
+        Object::Array(ref mut values) => {
+            for value in values {
+                normalize_strings(value);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The name of the file `Form::set_embed_values_on_save` attaches the values of the fields as
+const VALUES_ATTACHMENT: &str = "form-data.json";
+
+/// The names of the standard 14 fonts, which viewers provide without the document embedding them
+const STANDARD_FONTS: [&str; 14] = [
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+/// Quotes a string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a CSV value if it contains a delimiter, quote or line break
+fn csv_escape(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Splits a CSV line into its values, unquoting quoted values
+fn parse_csv_row(row: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = row.trim_end_matches(&['\r', '\n'][..]).chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    value.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if value.is_empty() => quoted = true,
+            ',' if !quoted => values.push(std::mem::take(&mut value)),
+            _ => value.push(c),
+        }
+    }
+    values.push(value);
+    values
+}
+
+/// Generates 16 unpredictable bytes for a document identifier
+fn random_id() -> Vec<u8> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    // Each `RandomState` is seeded with fresh random keys
+    let mut id = Vec::with_capacity(16);
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        id.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+    id
+}
+
+/// Formats the current time as a PDF date string in UTC, e.g. `D:20190101120000Z`
+fn pdf_date_now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Formats a number as lowercase roman numerals
+fn to_roman(mut number: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for &(value, numeral) in NUMERALS.iter() {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
+
+/// Formats a number as lowercase page label letters: a to z, then aa to zz, and so on
+fn to_letters(number: i64) -> String {
+    if number < 1 {
+        return String::new();
+    }
+    let letter = (b'a' + ((number - 1) % 26) as u8) as char;
+    let count = ((number - 1) / 26 + 1) as usize;
+    letter.to_string().repeat(count)
+}
+
+/// Reads a numeric object, which may be an integer or a real
+fn as_number(object: &Object) -> Option<f64> {
+    match *object {
+        Object::Integer(value) => Some(value as f64),
+        Object::Real(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Reads a rectangle array as `[left, bottom, right, top]`, whichever corners it gives
+fn as_rect(object: &Object) -> Option<[f64; 4]> {
+    let coords: Vec<f64> = object
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(as_number)
+        .collect();
+    if coords.len() != 4 {
+        return None;
+    }
+    Some([
+        coords[0].min(coords[2]),
+        coords[1].min(coords[3]),
+        coords[0].max(coords[2]),
+        coords[1].max(coords[3]),
+    ])
+}
+
+/// Gets the length of a file without the whitespace at its end
+fn content_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rposition(|&b| !b"\0\t\n\x0C\r ".contains(&b))
+        .map(|last| last + 1)
+        .unwrap_or(0)
+}
+
+fn quadding_from_i64(q: i64) -> Quadding {
+    match q {
+        1 => Quadding::Center,
+        2 => Quadding::Right,
+        _ => Quadding::Left,
+    }
+}
+
+fn quadding_to_i64(quadding: Quadding) -> i64 {
+    match quadding {
+        Quadding::Left => 0,
+        Quadding::Center => 1,
+        Quadding::Right => 2,
+    }
+}
+
+/// Appends `suffix` to the partial name "T" of a field dictionary
+fn rename_with_suffix(field: &mut Dictionary, suffix: &str) {
+    let mut name = field
+        .get(b"T")
+        .and_then(Object::as_str)
+        .map(decode_text)
+        .unwrap_or_default();
+    name.push_str(suffix);
+    field.set(
+        "T",
+        Object::String(encode_text(&name), StringFormat::Literal),
+    );
+}
+
+/// Returns true if the dictionary is a JavaScript action
+fn is_javascript_action(dict: &Dictionary) -> bool {
+    dict.get(b"S")
+        .and_then(Object::as_name)
+        .map(|kind| kind == b"JavaScript")
+        .unwrap_or(false)
+}
+
+/// Gets the actions an action chains to with "Next", which holds either one action or an array
+fn next_actions(action: &Dictionary) -> Vec<Object> {
+    match action.get(b"Next") {
+        Ok(Object::Array(actions)) => actions.clone(),
+        Ok(next) => vec![next.clone()],
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Gets what is left of an action once JavaScript actions are removed. A script action gives
+/// way to the actions it chains to, which are stripped in turn. `removed` maps the ids of the
+/// script action objects that were removed to the actions they chained to.
+fn splice_actions(
+    action: &Object,
+    removed: &BTreeMap<ObjectId, Vec<Object>>,
+    seen: &mut BTreeSet<ObjectId>,
+) -> Vec<Object> {
+    match *action {
+        Object::Reference(id) => match removed.get(&id) {
+            // Scripts may chain to each other in a loop
+            Some(next) if seen.insert(id) => next
+                .iter()
+                .flat_map(|next| splice_actions(next, removed, seen))
+                .collect(),
+            Some(_) => Vec::new(),
+            None => vec![action.clone()],
+        },
+        Object::Dictionary(ref dict) if is_javascript_action(dict) => next_actions(dict)
+            .iter()
+            .flat_map(|next| splice_actions(next, removed, seen))
+            .collect(),
+        Object::Dictionary(ref dict) => {
+            let mut dict = dict.clone();
+            strip_next_actions(&mut dict, removed);
+            vec![Object::Dictionary(dict)]
+        }
+        _ => vec![action.clone()],
+    }
+}
+
+/// Removes the JavaScript actions from the chain ("Next") of an action
+fn strip_next_actions(action: &mut Dictionary, removed: &BTreeMap<ObjectId, Vec<Object>>) {
+    if !action.has(b"S") || !action.has(b"Next") {
+        return;
+    }
+    let mut next: Vec<Object> = next_actions(action)
+        .iter()
+        .flat_map(|next| splice_actions(next, removed, &mut BTreeSet::new()))
+        .collect();
+    match next.len() {
+        0 => {
+            action.remove(b"Next");
+        }
+        1 => action.set("Next", next.remove(0)),
+        _ => action.set("Next", Object::Array(next)),
+    }
+}
+
+/// Removes the JavaScript actions from the entries of a dictionary that hold an action: the
+/// click action ("A"), the open action, the chain ("Next") of an action, and the entries of
+/// additional-actions ("AA") dictionaries, whether inline or, if `is_additional_actions` is
+/// set, the dictionary itself. Entries left without an action are removed.
+fn strip_script_actions(
+    dict: &mut Dictionary,
+    is_additional_actions: bool,
+    removed: &BTreeMap<ObjectId, Vec<Object>>,
+    doc: &Document,
+) {
+    let mut keys = vec![b"A".to_vec(), b"OpenAction".to_vec()];
+    if is_additional_actions {
+        keys.extend(dict.iter().map(|(key, _)| key.clone()));
+    }
+    for key in keys {
+        let mut actions = match dict.get(&key) {
+            // An open action may be a destination instead of an action
+            Ok(&Object::Array(_)) | Err(_) => continue,
+            Ok(action) => splice_actions(action, removed, &mut BTreeSet::new()),
+        };
+        if actions.len() > 1 {
+            // The entry takes a single action, so the others are chained to a copy of the first,
+            // after the actions it already chains to
+            let rest = actions.split_off(1);
+            if let Ok(first) = actions[0].resolve(doc).as_dict() {
+                let mut first = first.clone();
+                strip_next_actions(&mut first, removed);
+                let mut next = next_actions(&first);
+                next.extend(rest);
+                first.set("Next", Object::Array(next));
+                actions[0] = Object::Dictionary(first);
+            }
+        }
+        match actions.into_iter().next() {
+            Some(action) => dict.set(key, action),
+            None => {
+                dict.remove(&key);
+            }
+        }
+    }
+    let mut no_actions_left = false;
+    if let Ok(&mut Object::Dictionary(ref mut actions)) = dict.get_mut(b"AA") {
+        strip_script_actions(actions, true, removed, doc);
+        no_actions_left = actions.iter().next().is_none();
+    }
+    if no_actions_left {
+        dict.remove(b"AA");
+    }
+    strip_next_actions(dict, removed);
 }
 
 impl Form {
     /// Takes a reader containing a PDF with a fillable form, analyzes the content, and attempts to
     /// identify all of the fields the form has.
-    pub fn load_from<R: io::Read>(reader: R) -> Result<Self, LoadError> {
-        let doc = Document::load_from(reader).unwrap();
-        Self::load_doc(doc)
+    pub fn load_from<R: io::Read>(mut reader: R) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let doc = Document::load_from(&bytes[..])?;
+        let mut form = Self::load_doc(doc, &Limits::default())?;
+        form.source_len = Some(content_len(&bytes));
+        Ok(form)
+    }
+
+    /// Like `load_from`, but gives up with `LoadError::LimitExceeded` as soon as the document
+    /// goes beyond any of the `limits`, to protect services from exhausting their resources on
+    /// huge or maliciously crafted uploads.
+    pub fn load_with_limits<R: io::Read>(reader: R, limits: Limits) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        // Read one byte past the limit to tell whether the document is larger than it
+        let max_bytes = (limits.max_bytes as u64).saturating_add(1);
+        reader.take(max_bytes).read_to_end(&mut bytes)?;
+        if bytes.len() > limits.max_bytes {
+            return Err(LoadError::LimitExceeded);
+        }
+        let doc = Document::load_from(&bytes[..])?;
+        let mut form = Self::load_doc(doc, &limits)?;
+        form.source_len = Some(content_len(&bytes));
+        Ok(form)
     }
 
     /// Takes a path to a PDF with a fillable form, analyzes the file, and attempts to identify all
     /// of the fields the form has.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
-        let doc = Document::load(path).unwrap();
-        Self::load_doc(doc)
+        Self::load_from(File::open(path)?)
+    }
+
+    /// Like `load`, but only keeps track of the fields whose full names are in `names`, for
+    /// filling a few fields of a large form. The indices of the form refer to these fields
+    /// alone, in document order. The other fields are kept in the document untouched.
+    pub fn load_fields<P: AsRef<Path>>(path: P, names: &[&str]) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let doc = Document::load_from(&bytes[..])?;
+        let mut form = Self::load_doc_fields(doc, &Limits::default(), Some(names))?;
+        form.source_len = Some(content_len(&bytes));
+        Ok(form)
     }
 
-    fn load_doc(doc: Document) -> Result<Self, LoadError> {
+    fn load_doc(doc: Document, limits: &Limits) -> Result<Self, LoadError> {
+        Self::load_doc_fields(doc, limits, None)
+    }
+
+    /// Walks the field tree of the document. When `names` is given, only the fields
+    /// whose full names are in it are kept track of.
+    fn load_doc_fields(
+        mut doc: Document,
+        limits: &Limits,
+        names: Option<&[&str]>,
+    ) -> Result<Self, LoadError> {
+        // The catalog may be given directly in the trailer. Move it into an object of its own, as
+        // lopdf and the rest of this crate expect to find it by reference.
+        let direct_root = match doc.trailer.get(b"Root") {
+            Ok(Object::Dictionary(catalog)) => Some(catalog.clone()),
+            _ => None,
+        };
+        if let Some(catalog) = direct_root {
+            let root_id = doc.add_object(catalog);
+            doc.trailer.set("Root", Object::Reference(root_id));
+        }
         let mut form_ids = Vec::new();
-        let mut queue = VecDeque::new();
+        let mut stack = Vec::new();
         // Block so borrow of doc ends before doc is moved into the result
         {
             // Get the form's top level fields
-            let catalog = doc.catalog().unwrap();
-                
-            let acroform = catalog.get_deref(b"AcroForm",&doc).unwrap();
+            let catalog = doc
+                .catalog()
+                .map_err(|_| LoadError::DictionaryKeyNotFound)?;
+            // The interactive form dictionary may be kept in the catalog or in an object of its own
+            let acroform = match catalog.get(b"AcroForm") {
+                Ok(acroform @ &Object::Reference(_)) => acroform.deref(&doc)?,
+                Ok(acroform) => acroform,
+                Err(_) => return Err(LoadError::DictionaryKeyNotFound),
+            };
 
-            println!("{:?}", acroform);
-            
-            //.unwrap();
+            // "Fields" may be kept in an object of its own
+            let fields_list = acroform
+                .as_dict()
+                .map_err(|_| LoadError::UnexpectedType)?
+                .get(b"Fields")
+                .map_err(|_| LoadError::DictionaryKeyNotFound)?
+                .resolve(&doc)
+                .as_array()
+                .map_err(|_| LoadError::UnexpectedType)?;
 
-            let fields_list = acroform.as_dict().unwrap().get(b"Fields").unwrap().as_array().unwrap();
-
-            queue.append(&mut VecDeque::from(fields_list.clone()));
+            // Fields are pushed in reverse so they are popped in the order they were authored,
+            // along with their depth in the field tree and the full name of their parent
+            stack.extend(
+                fields_list
+                    .iter()
+                    .rev()
+                    .map(|field| (field.clone(), 1, None)),
+            );
 
-            // Iterate over the fields
-            while let Some(objref) = queue.pop_front() {
+            // Walk the fields depth first, so that the indices follow the document's order, and
+            // skip any seen before in case "Kids" form a loop
+            let mut visited = BTreeSet::new();
+            while let Some((objref, depth, parent_name)) = stack.pop() {
+                if let Ok(id) = objref.as_reference() {
+                    if !visited.insert(id) {
+                        continue;
+                    }
+                }
+                if visited.len() > limits.max_fields || depth > limits.max_depth {
+                    return Err(LoadError::LimitExceeded);
+                }
                 let obj = objref.deref(&doc)?;
-                if let &Object::Dictionary(ref dict) = obj {
+                if let Object::Dictionary(ref dict) = *obj {
+                    // A field without a partial name shares the full name of its parent
+                    let full_name: Option<String> =
+                        match dict.get(b"T").and_then(|t| t.resolve(&doc).as_str()) {
+                            Ok(name) => Some(match parent_name {
+                                Some(parent_name) => {
+                                    format!("{}.{}", parent_name, decode_text(name))
+                                }
+                                None => decode_text(name),
+                            }),
+                            Err(_) => parent_name,
+                        };
+                    let wanted = match (names, full_name.as_deref()) {
+                        (Some(names), Some(full_name)) => names.contains(&full_name),
+                        (Some(_), None) => false,
+                        (None, _) => true,
+                    };
                     // If the field has FT, it actually takes input.  Save this
-                    match dict.get(b"FT") {
-                        Ok(f) => form_ids.push(objref.as_reference().unwrap()),
-                        _ => (),
+                    if dict.has(b"FT") && wanted {
+                        form_ids.push(
+                            objref
+                                .as_reference()
+                                .map_err(|_| LoadError::NotAReference)?,
+                        );
                     }
-                    // If this field has kids, they might have FT, so add them to the queue
-                    match dict.get(b"Kids") {
-                        Ok(f) => queue.append(&mut VecDeque::from(f.clone().as_array().unwrap().clone())),
-                        _ => (),
+                    // If this field has kids, they might have FT, so visit them next
+                    if let Ok(kids) = dict.get(b"Kids") {
+                        let kids = kids
+                            .resolve(&doc)
+                            .as_array()
+                            .map_err(|_| LoadError::UnexpectedType)?;
+                        stack.extend(
+                            kids.iter()
+                                .rev()
+                                .map(|kid| (kid.clone(), depth + 1, full_name.clone())),
+                        );
                     }
                 }
             }
         }
-        Ok(Form { doc, form_ids })
+        Ok(Form {
+            doc,
+            form_ids,
+            track_field_modifications: false,
+            clear_xfa_on_change: false,
+            regenerate_id_on_save: false,
+            preserve_appearance_font: false,
+            embed_values_on_save: false,
+            trim_text: false,
+            source_len: None,
+        })
     }
 
     /// Returns the number of fields the form has
@@ -180,8 +970,18 @@ impl Form {
         self.form_ids.len()
     }
 
+    /// Returns true if the form has no fields
+    pub fn is_empty(&self) -> bool {
+        self.form_ids.is_empty()
+    }
+
     /// Gets the type of field of the given index
     ///
+    /// Choice fields are combo boxes if their Combo flag is set. Without it, the Edit flag, which
+    /// only combo boxes have, still makes them combo boxes, unless they also have something only
+    /// list boxes have: the MultiSelect flag or a top index ("TI"). All other choice fields are
+    /// list boxes.
+    ///
     /// # Panics
     /// This function will panic if the index is greater than the number of fields
     pub fn get_type(&self, n: usize) -> FieldType {
@@ -210,7 +1010,10 @@ impl Form {
             let flags = ChoiceFlags::from_bits_truncate(
                 field.get(b"Ff").unwrap_or(&obj_zero).as_i64().unwrap() as u32,
             );
-            if flags.intersects(ChoiceFlags::COBMO) {
+            let list_only = flags.intersects(ChoiceFlags::MULTISELECT) || field.has(b"TI");
+            if flags.intersects(ChoiceFlags::COBMO)
+                || (flags.intersects(ChoiceFlags::EDIT) && !list_only)
+            {
                 FieldType::ComboBox
             } else {
                 FieldType::ListBox
@@ -241,6 +1044,212 @@ impl Form {
         }
     }
 
+    /// Gets the fully qualified name of the field of the given index, which joins the partial
+    /// names of the field and its ancestors with periods, e.g. "address.city"
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_full_name(&self, n: usize) -> Option<String> {
+        let mut names = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut node_id = self.form_ids[n];
+        // A malformed form could have a "Parent" chain that loops back on itself, so stop at the
+        // first field seen twice
+        while visited.insert(node_id) {
+            let node = match self.doc.objects.get(&node_id).map(Object::as_dict) {
+                Some(Ok(node)) => node,
+                _ => break,
+            };
+            if let Ok(name) = node.get(b"T").and_then(|t| t.resolve(&self.doc).as_str()) {
+                names.push(decode_text(name));
+            }
+            match node.get(b"Parent").and_then(Object::as_reference) {
+                Ok(parent_id) => node_id = parent_id,
+                Err(_) => break,
+            }
+        }
+        if names.is_empty() {
+            return None;
+        }
+        names.reverse();
+        Some(names.join("."))
+    }
+
+    /// Gets the mapping name ("TM") of the field of the given index, which is the name to use
+    /// when exporting the field's data in place of its partial name
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_mapping_name(&self, n: usize) -> Option<String> {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        field
+            .get(b"TM")
+            .ok()?
+            .resolve(&self.doc)
+            .as_str()
+            .ok()
+            .map(decode_text)
+    }
+
+    /// Returns true if the field of the given index is a text field whose value may contain rich
+    /// text (the RichText flag is set)
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn is_rich_text(&self, n: usize) -> bool {
+        match self.get_type(n) {
+            FieldType::Text => self.get_text_flags(n).contains(TextFlags::RICH_TEXT),
+            _ => false,
+        }
+    }
+
+    /// If the field of the given index is a comb text field, gets its value as the characters of
+    /// its "MaxLen" cells, one per cell. Empty cells are spaces, and characters beyond the last
+    /// cell are dropped. Returns `None` for any other field.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_comb_cells(&self, n: usize) -> Option<Vec<char>> {
+        if let FieldType::Text = self.get_type(n) {
+            let max_len = self.get_comb_max_len(n)?;
+            let text = match self.get_state(n) {
+                FieldState::Text { text } => text,
+                _ => String::new(),
+            };
+            Some(
+                text.chars()
+                    .chain(std::iter::repeat(' '))
+                    .take(max_len)
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Gets the justification of the text of the field of the given index. Fields that don't set
+    /// "Q" themselves inherit it from their ancestors or the form default, and are otherwise left
+    /// justified.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_quadding(&self, n: usize) -> Quadding {
+        self.get_inheritable(n, b"Q")
+            .and_then(|q| q.as_i64().ok())
+            .map(quadding_from_i64)
+            .or_else(|| self.get_form_quadding())
+            .unwrap_or(Quadding::Left)
+    }
+
+    /// Gets the default justification ("Q") the form sets for fields that don't specify one
+    pub fn get_form_quadding(&self) -> Option<Quadding> {
+        self.get_acroform()?
+            .get(b"Q")
+            .and_then(Object::as_i64)
+            .ok()
+            .map(quadding_from_i64)
+    }
+
+    /// Gets the default style string ("DS") of a rich text field, a CSS-like declaration such as
+    /// `font: 12pt Helvetica`
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_default_style(&self, n: usize) -> Option<String> {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        field
+            .get(b"DS")
+            .ok()?
+            .resolve(&self.doc)
+            .as_str()
+            .ok()
+            .map(decode_text)
+    }
+
+    /// Gets the font, font size and text color the field's text is shown with, from its default
+    /// appearance ("DA"), which may be inherited from a parent field or the form
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_appearance_settings(&self, n: usize) -> Option<AppearanceSettings> {
+        let da = DefaultAppearance::parse(&self.get_default_appearance(n)?)?;
+        Some(AppearanceSettings {
+            font: da.font,
+            font_size: da.size,
+            color: da.color,
+        })
+    }
+
+    /// Returns true if the font the field's default appearance ("DA") selects can be found: in
+    /// the form's default resources ("DR"), or as one of the standard 14 fonts every viewer has.
+    /// Appearances can't be generated for the field otherwise.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn da_font_available(&self, n: usize) -> bool {
+        match self
+            .get_default_appearance(n)
+            .and_then(|da| DefaultAppearance::parse(&da))
+        {
+            Some(da) => {
+                self.get_form_font(&da.font).is_some() || STANDARD_FONTS.contains(&da.font.as_str())
+            }
+            None => false,
+        }
+    }
+
+    /// Gets the background of the field's widget. Returns `None` if the widget has a transparent
+    /// background or doesn't specify one.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_widget_background(&self, n: usize) -> Option<WidgetBackground> {
+        let background = self
+            .get_widget(n)
+            .get_deref(b"MK", &self.doc)
+            .and_then(Object::as_dict)
+            .and_then(|mk| mk.get(b"BG"))
+            .ok()?;
+        let is_pattern = |object: &Object| {
+            matches!(
+                *object.resolve(&self.doc),
+                Object::Name(_) | Object::Dictionary(_) | Object::Stream(_)
+            )
+        };
+        let components = match *background.resolve(&self.doc) {
+            Object::Array(ref components) => components,
+            ref other if is_pattern(other) => return Some(WidgetBackground::Pattern),
+            _ => return None,
+        };
+        // A pattern may also be given along with the components of its underlying color space
+        if components.iter().any(is_pattern) {
+            return Some(WidgetBackground::Pattern);
+        }
+        let values: Vec<f64> = components
+            .iter()
+            .filter_map(|component| as_number(component.resolve(&self.doc)))
+            .collect();
+        let color = match values.len() {
+            1 => Color::Gray(values[0]),
+            3 => Color::Rgb(values[0], values[1], values[2]),
+            4 => Color::Cmyk(values[0], values[1], values[2], values[3]),
+            _ => return None,
+        };
+        Some(WidgetBackground::Color(color))
+    }
+
     /// Gets the types of all of the fields in the form
     pub fn get_all_types(&self) -> Vec<FieldType> {
         let mut res = Vec::with_capacity(self.len());
@@ -250,6 +1259,12 @@ impl Form {
         res
     }
 
+    /// Counts the fields of type `ty`, without collecting the types of all fields like
+    /// `get_all_types` does
+    pub fn count_of(&self, ty: FieldType) -> usize {
+        (0..self.len()).filter(|&i| self.get_type(i) == ty).count()
+    }
+
     /// Gets the names of all of the fields in the form
     pub fn get_all_names(&self) -> Vec<Option<String>> {
         let mut res = Vec::with_capacity(self.len());
@@ -259,50 +1274,3942 @@ impl Form {
         res
     }
 
-    /// If the field at index `n` is a text field, fills in that field with the text `s`.
-    /// If it is not a text field, returns ValueError
+    /// Gets the number of distinct full field names in the form. Fields sharing a full name are
+    /// counted once, so this is the number of logical fields, while `len` counts every field.
+    /// Fields without a name are each counted on their own.
+    pub fn distinct_field_count(&self) -> usize {
+        let mut names = BTreeSet::new();
+        let mut unnamed = 0;
+        for n in 0..self.len() {
+            match self.get_full_name(n) {
+                Some(name) => {
+                    names.insert(name);
+                }
+                None => unnamed += 1,
+            }
+        }
+        names.len() + unnamed
+    }
+
+    /// Gets the state of field of the given index
+    ///
+    /// The selection of list and combo boxes is read from the selected indices ("I") when they
+    /// point at valid options, since viewers track the selection by them, and from the value
+    /// ("V") otherwise.
     ///
     /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_text(&mut self, n: usize, s: String) -> Result<(), ValueError> {
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_state(&self, n: usize) -> FieldState {
         match self.get_type(n) {
-            FieldType::Text => {
-                let field = self
-                    .doc
-                    .objects
+            FieldType::Button => FieldState::Button,
+            FieldType::Radio => FieldState::Radio {
+                selected: match self.get_inheritable(n, b"V") {
+                    Some(Object::Name(name)) => String::from_utf8_lossy(name).into_owned(),
+                    // Without a value, the selected button is the one showing an on state
+                    _ => self
+                        .selected_widget_state(n)
+                        .map(|(_, state)| state)
+                        .unwrap_or_default(),
+                },
+                options: self.get_radio_options(n),
+            },
+            FieldType::CheckBox => FieldState::CheckBox {
+                is_checked: match self.get_inheritable(n, b"V") {
+                    Some(Object::Name(name)) => !name.is_empty() && name != b"Off",
+                    // Without a value, the box is checked if its widget shows an on state
+                    _ => self.selected_widget_state(n).is_some(),
+                },
+            },
+            FieldType::ListBox => FieldState::ListBox {
+                selected: self.get_selection(n),
+                options: self.get_options(n),
+                multiselect: self
+                    .get_choice_flags(n)
+                    .intersects(ChoiceFlags::MULTISELECT),
+                commit_on_change: self
+                    .get_choice_flags(n)
+                    .intersects(ChoiceFlags::COMMIT_ON_CHANGE),
+            },
+            FieldType::ComboBox => FieldState::ComboBox {
+                selected: self.get_selection(n),
+                options: self.get_options(n),
+                editable: self.get_choice_flags(n).intersects(ChoiceFlags::EDIT),
+                commit_on_change: self
+                    .get_choice_flags(n)
+                    .intersects(ChoiceFlags::COMMIT_ON_CHANGE),
+            },
+            FieldType::Text => FieldState::Text {
+                text: match self.get_inheritable(n, b"V") {
+                    Some(Object::String(text, _)) => decode_text(text),
+                    _ => String::new(),
+                },
+            },
+        }
+    }
+
+    /// If the field of the given index is a radio button group or a check box, gets the position
+    /// among its widgets ("Kids") of the one that is switched on, going by their appearance states
+    /// ("AS"). Returns `None` if none is on, or for other fields.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn selected_widget(&self, n: usize) -> Option<usize> {
+        match self.get_type(n) {
+            FieldType::Radio | FieldType::CheckBox => self.selected_widget_state(n).map(|(i, _)| i),
+            _ => None,
+        }
+    }
+
+    /// If the field of the given index is a radio button group or a check box, gets its options
+    /// as pairs of export value (the on state of a widget) and label. A widget's label is its
+    /// entry in the field's "Opt" array, which lists one text string per widget, or its on state
+    /// if there is none. Returns an empty list for other fields.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn radio_option_pairs(&self, n: usize) -> Vec<(String, String)> {
+        match self.get_type(n) {
+            FieldType::Radio | FieldType::CheckBox => (),
+            _ => return Vec::new(),
+        }
+        let labels: Vec<Option<String>> = self
+            .get_inheritable(n, b"Opt")
+            .and_then(|labels| labels.as_array().ok())
+            .into_iter()
+            .flatten()
+            .map(|label| label.resolve(&self.doc).as_str().ok().map(decode_text))
+            .collect();
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        for (i, widget_id) in self.get_widget_ids(n).into_iter().enumerate() {
+            for state in self.get_widget_states(widget_id) {
+                if pairs.iter().any(|(export, _)| *export == state) {
+                    continue;
+                }
+                let label = labels
+                    .get(i)
+                    .cloned()
+                    .flatten()
+                    .unwrap_or_else(|| state.clone());
+                pairs.push((state, label));
+            }
+        }
+        pairs
+    }
+
+    /// If the field of the given index is a checked check box, gets the name of the on state it
+    /// is set to, such as "Yes", which is the value the box exports. Returns `None` if the box
+    /// is unchecked or the field isn't a check box.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn checkbox_export_value(&self, n: usize) -> Option<String> {
+        match self.get_state(n) {
+            FieldState::CheckBox { is_checked: true } => match self.get_inheritable(n, b"V") {
+                Some(Object::Name(state)) => Some(String::from_utf8_lossy(state).into_owned()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns true if the fields at indices `a` and `b` are both radio buttons of the same
+    /// group. Buttons without a name of their own ("T") belong to the group of their parent, so
+    /// they are in the same group if they share it.
+    ///
+    /// # Panics
+    /// This function will panic if either index is greater than the number of fields
+    pub fn same_radio_group(&self, a: usize, b: usize) -> bool {
+        match (self.get_type(a), self.get_type(b)) {
+            (FieldType::Radio, FieldType::Radio) => {
+                self.get_radio_group_id(a) == self.get_radio_group_id(b)
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets the distinct export names of the fields, in form order, for use as the headers of
+    /// CSV files with `export_csv_row` and `apply_csv_row`. A field's export name is its mapping
+    /// name ("TM") when it has one, and its full name otherwise.
+    pub fn csv_headers(&self) -> Vec<String> {
+        let mut headers: Vec<String> = Vec::new();
+        for n in 0..self.len() {
+            if let Some(name) = self.get_export_name(n) {
+                if !headers.contains(&name) {
+                    headers.push(name);
+                }
+            }
+        }
+        headers
+    }
+
+    /// Formats the values of the fields with the export names in `headers` as a CSV line
+    /// (without a line ending), in the order of the headers. Values are left blank for names no
+    /// field has.
+    /// Check boxes give their on state when checked and "Off" otherwise, and the selections of
+    /// list boxes are separated by semicolons.
+    pub fn export_csv_row(&self, headers: &[&str]) -> String {
+        headers
+            .iter()
+            .map(|header| match self.indices_by_export_name(header).first() {
+                Some(&n) => csv_escape(&self.get_value_text(n)),
+                None => String::new(),
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    /// Hashes the values of the fields by their full names, in name order, so that forms with
+    /// the same contents hash the same however their objects are laid out, e.g. to cache
+    /// output rendered from them. Values are taken as `export_csv_row` writes them. The hash is
+    /// stable between runs, but may change with the Rust version the crate is built with.
+    pub fn values_hash(&self) -> u64 {
+        let mut names: Vec<String> = Vec::new();
+        for n in 0..self.len() {
+            if let Some(name) = self.get_full_name(n) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        let mut hasher = DefaultHasher::new();
+        for name in names {
+            let value = match self.indices_by_name(&name).first() {
+                Some(&n) => self.get_value_text(n),
+                None => String::new(),
+            };
+            (name, value).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Counts how many of the fields have been filled in. Returns `(filled, total)`, where push
+    /// buttons are left out of both because they hold no value.
+    pub fn completion(&self) -> (usize, usize) {
+        let mut filled = 0;
+        let mut total = 0;
+        for i in 0..self.len() {
+            if let FieldType::Button = self.get_type(i) {
+                continue;
+            }
+            total += 1;
+            if self.is_filled(i) {
+                filled += 1;
+            }
+        }
+        (filled, total)
+    }
+
+    /// Checks whether the field at index `n` holds a non-empty value for its type, as read by
+    /// `get_state`. Unchecked check boxes and radio groups with nothing selected count as empty.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    fn is_filled(&self, n: usize) -> bool {
+        match self.get_state(n) {
+            FieldState::Button => false,
+            FieldState::Radio { selected, .. } => !selected.is_empty() && selected != "Off",
+            FieldState::CheckBox { is_checked } => is_checked,
+            FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } => {
+                selected.iter().any(|selected| !selected.is_empty())
+            }
+            FieldState::Text { text } => !text.is_empty(),
+        }
+    }
+
+    /// Checks the form for structural problems that keep fields from working as expected:
+    /// fields without a widget, check boxes without an on state in their normal appearance
+    /// ("AP") or set to a state it doesn't have, choice fields without options, text and choice
+    /// fields without a default appearance ("DA"), fields sharing a full name, and references to
+    /// objects that don't exist. Returns an empty list if none are found.
+    pub fn audit(&self) -> Vec<FormIssue> {
+        let mut issues = Vec::new();
+        let mut names: HashMap<String, usize> = HashMap::new();
+        for n in 0..self.len() {
+            let name = self.get_full_name(n);
+            let mut report = |description: String| {
+                issues.push(FormIssue {
+                    field: n,
+                    name: name.clone(),
+                    description,
+                })
+            };
+            let widget_ids = self.get_widget_ids(n);
+            let has_widget = widget_ids.iter().any(|id| {
+                self.doc
+                    .objects
+                    .get(id)
+                    .and_then(|widget| widget.as_dict().ok())
+                    .map(|widget| widget.has(b"Rect"))
+                    .unwrap_or(false)
+            });
+            if !has_widget {
+                report("field has no widget annotation".to_owned());
+            }
+            match self.get_type(n) {
+                FieldType::CheckBox => {
+                    // Any name can be the on state, as long as the appearance has it
+                    let states = self.get_radio_options(n);
+                    match self.get_inheritable(n, b"V") {
+                        _ if states.is_empty() => report(
+                            "check box has no on state in its normal appearance (\"AP\")"
+                                .to_owned(),
+                        ),
+                        Some(Object::Name(value))
+                            if value != b"Off"
+                                && !states.iter().any(|state| state.as_bytes() == &value[..]) =>
+                        {
+                            report(format!(
+                                "check box is set to \"{}\", which its appearance has no state for",
+                                String::from_utf8_lossy(value)
+                            ))
+                        }
+                        _ => (),
+                    }
+                }
+                FieldType::ListBox | FieldType::ComboBox => {
+                    let editable = self.get_choice_flags(n).contains(ChoiceFlags::EDIT);
+                    if self.get_options(n).is_empty() && !editable {
+                        report("choice field has no options (\"Opt\")".to_owned());
+                    }
+                }
+                _ => (),
+            }
+            match self.get_type(n) {
+                FieldType::Text | FieldType::ListBox | FieldType::ComboBox
+                    if self.get_default_appearance(n).is_none() =>
+                {
+                    report("field has no default appearance (\"DA\")".to_owned())
+                }
+                _ => (),
+            }
+            if let Some(ref name) = name {
+                if let Some(&first) = names.get(name) {
+                    report(format!("field has the same full name as field {}", first));
+                }
+            }
+            let mut missing = BTreeSet::new();
+            let mut ids = widget_ids;
+            ids.push(self.form_ids[n]);
+            for id in ids {
+                if let Some(object) = self.doc.objects.get(&id) {
+                    self.find_missing_references(object, &mut missing);
+                }
+            }
+            for (id, generation) in missing {
+                report(format!(
+                    "reference {} {} R points to no object",
+                    id, generation
+                ));
+            }
+            if let Some(name) = name {
+                names.entry(name).or_insert(n);
+            }
+        }
+        issues
+    }
+
+    /// Gets the indices of the fields whose value ("V") is stored as the wrong type of object for
+    /// the field: text and choice field values must be strings (or arrays of strings for choice
+    /// fields allowing several selections), and check box and radio button values must be names.
+    /// Fields without a value aren't reported.
+    pub fn validate_value_types(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&n| !self.has_valid_value_type(n))
+            .collect()
+    }
+
+    /// Gets the indices of the fields that have a value ("V") but a widget without a normal
+    /// appearance ("N" in "AP"), such as text fields filled in with `set_text`, which removes
+    /// the outdated appearance, or check boxes and radio buttons showing an on state ("AS") their
+    /// normal appearance has no entry for. These need `generate_appearance`, or the form's
+    /// "NeedAppearances" flag set so that viewers generate them.
+    pub fn stale_appearances(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&n| self.get_inheritable(n, b"V").is_some())
+            .filter(|&n| {
+                let field_type = self.get_type(n);
+                let is_button = field_type == FieldType::CheckBox || field_type == FieldType::Radio;
+                self.get_widget_ids(n).into_iter().any(|widget_id| {
+                    let widget = match self
+                        .doc
+                        .objects
+                        .get(&widget_id)
+                        .and_then(|widget| widget.as_dict().ok())
+                    {
+                        Some(widget) => widget,
+                        None => return true,
+                    };
+                    let normal = widget
+                        .get(b"AP")
+                        .ok()
+                        .and_then(|ap| ap.resolve(&self.doc).as_dict().ok())
+                        .and_then(|ap| ap.get(b"N").ok());
+                    let state = widget.get(b"AS").and_then(Object::as_name);
+                    match (normal.map(|normal| normal.resolve(&self.doc)), state) {
+                        (None, _) => true,
+                        (Some(Object::Dictionary(states)), Ok(state)) if is_button => {
+                            state != b"Off" && !states.has(state)
+                        }
+                        _ => false,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Repairs the values found by `validate_value_types` where the intended value is clear:
+    /// names, numbers and booleans become strings for text and choice fields, and strings become
+    /// names for check boxes and radio buttons. Returns the indices of the fields whose values
+    /// couldn't be repaired, which are left as they are.
+    pub fn coerce_value_types(&mut self) -> Vec<usize> {
+        let mut unrepaired = Vec::new();
+        for n in self.validate_value_types() {
+            let owner_id = match self.get_inheritable_owner(n, b"V") {
+                Some(owner_id) => owner_id,
+                None => continue,
+            };
+            let value = self.get_inheritable(n, b"V").unwrap().clone();
+            let as_string = |value: &Object| {
+                let text = match *value {
+                    Object::String(..) => return Some(value.clone()),
+                    Object::Name(ref name) => name.clone(),
+                    Object::Integer(number) => number.to_string().into_bytes(),
+                    Object::Real(number) => number.to_string().into_bytes(),
+                    Object::Boolean(boolean) => boolean.to_string().into_bytes(),
+                    _ => return None,
+                };
+                Some(Object::String(text, StringFormat::Literal))
+            };
+            let repaired = match self.get_type(n) {
+                FieldType::Text => as_string(&value),
+                FieldType::ListBox | FieldType::ComboBox => match value {
+                    Object::Array(ref values) => values
+                        .iter()
+                        .map(|value| as_string(value.resolve(&self.doc)))
+                        .collect::<Option<Vec<Object>>>()
+                        .map(Object::Array),
+                    ref value => as_string(value),
+                },
+                FieldType::CheckBox | FieldType::Radio => match value {
+                    Object::String(ref text, _) => {
+                        Some(Object::Name(encode_text(&decode_text(text))))
+                    }
+                    _ => None,
+                },
+                FieldType::Button => None,
+            };
+            match repaired {
+                Some(repaired) => {
+                    let owner = self
+                        .doc
+                        .objects
+                        .get_mut(&owner_id)
+                        .unwrap()
+                        .as_dict_mut()
+                        .unwrap();
+                    owner.set("V", repaired);
+                }
+                None => unrepaired.push(n),
+            }
+        }
+        unrepaired
+    }
+
+    /// Gets the normal appearance stream of the field's widget, decompressed
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_appearance_stream(&self, n: usize) -> Option<Vec<u8>> {
+        self.get_appearance_stream_variant(n, AppearanceVariant::Normal)
+    }
+
+    /// Gets the given appearance stream of the field's widget, decompressed. If the variant is a
+    /// dictionary of states (as it is for check boxes and radio buttons), the stream for the
+    /// widget's current "AS" state is returned.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_appearance_stream_variant(
+        &self,
+        n: usize,
+        variant: AppearanceVariant,
+    ) -> Option<Vec<u8>> {
+        let widget = self.get_widget(n);
+        let key: &[u8] = match variant {
+            AppearanceVariant::Normal => b"N",
+            AppearanceVariant::Down => b"D",
+            AppearanceVariant::Rollover => b"R",
+        };
+        let ap = widget.get_deref(b"AP", &self.doc).ok()?.as_dict().ok()?;
+        let mut appearance = ap.get_deref(key, &self.doc).ok()?;
+        if let Object::Dictionary(ref states) = *appearance {
+            let state = widget.get(b"AS").ok()?.as_name().ok()?;
+            appearance = states.get_deref(state, &self.doc).ok()?;
+        }
+        let mut stream = appearance.as_stream().ok()?.clone();
+        stream.decompress();
+        Some(stream.content)
+    }
+
+    /// Gets the action the field's widget performs when clicked ("A"), which is usually only set on
+    /// push buttons
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_action(&self, n: usize) -> Option<ButtonAction> {
+        let action = self.get_widget(n).get(b"A").ok()?;
+        self.parse_action(action.resolve(&self.doc))
+    }
+
+    /// Gets the action the document performs when it is opened ("OpenAction" in the catalog).
+    /// An open action that is just a destination is reported as `ButtonAction::GoTo`.
+    pub fn get_open_action(&self) -> Option<ButtonAction> {
+        let action = self.doc.catalog().ok()?.get(b"OpenAction").ok()?;
+        match *action.resolve(&self.doc) {
+            Object::Array(_) => Some(ButtonAction::GoTo),
+            ref action => self.parse_action(action),
+        }
+    }
+
+    /// Gets whether the form is defined by AcroForm fields, by an XFA form ("XFA" in the
+    /// interactive form dictionary), or by both
+    pub fn form_kind(&self) -> FormKind {
+        let acroform = match self.get_acroform() {
+            Some(acroform) => acroform,
+            None => return FormKind::AcroForm,
+        };
+        if !acroform.has(b"XFA") {
+            return FormKind::AcroForm;
+        }
+        // Viewers are asked to render XFA-only forms from the XFA since they have no fields
+        let needs_rendering = self
+            .doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"NeedsRendering"))
+            .map(|needs_rendering| match *needs_rendering {
+                Object::Boolean(needs_rendering) => needs_rendering,
+                _ => false,
+            })
+            .unwrap_or(false);
+        if self.form_ids.is_empty() || needs_rendering {
+            FormKind::XfaOnly
+        } else {
+            FormKind::Hybrid
+        }
+    }
+
+    /// Gets the names of the keys in the interactive form dictionary, e.g. "Fields", "DR", "DA"
+    /// or "XFA", which show what the form uses
+    pub fn acroform_keys(&self) -> Vec<String> {
+        self.get_acroform()
+            .map(|acroform| {
+                acroform
+                    .iter()
+                    .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets the document's viewer preferences
+    pub fn viewer_preferences(&self) -> ViewerPreferences {
+        let preferences = self
+            .doc
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"ViewerPreferences").ok())
+            .and_then(|preferences| preferences.resolve(&self.doc).as_dict().ok());
+        let flag = |key: &[u8]| match preferences.map(|preferences| preferences.get(key)) {
+            Some(Ok(&Object::Boolean(value))) => value,
+            _ => false,
+        };
+        ViewerPreferences {
+            hide_toolbar: flag(b"HideToolbar"),
+            hide_menubar: flag(b"HideMenubar"),
+            display_doc_title: flag(b"DisplayDocTitle"),
+        }
+    }
+
+    /// Gets the names of the form's default resources ("DR") by category, e.g. "Font",
+    /// "XObject" or "ExtGState", so that appearances can reuse them instead of duplicating them.
+    /// The "ProcSet" category lists the procedure sets the resources declare.
+    pub fn form_resources(&self) -> HashMap<String, Vec<String>> {
+        let resources = match self
+            .get_acroform()
+            .and_then(|acroform| acroform.get(b"DR").ok())
+            .and_then(|resources| resources.resolve(&self.doc).as_dict().ok())
+        {
+            Some(resources) => resources,
+            None => return HashMap::new(),
+        };
+        resources
+            .iter()
+            .map(|(category, entries)| {
+                let names = match *entries.resolve(&self.doc) {
+                    Object::Dictionary(ref entries) => entries
+                        .iter()
+                        .map(|(name, _)| String::from_utf8_lossy(name).into_owned())
+                        .collect(),
+                    Object::Array(ref entries) => entries
+                        .iter()
+                        .filter_map(|name| name.as_name().ok())
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                (String::from_utf8_lossy(category).into_owned(), names)
+            })
+            .collect()
+    }
+
+    /// Gets the two parts of the document's identifier (the trailer's "ID"): the permanent one
+    /// assigned when the document was created, and the one that changes with every version
+    pub fn document_id(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let id = self
+            .doc
+            .trailer
+            .get(b"ID")
+            .ok()?
+            .resolve(&self.doc)
+            .as_array()
+            .ok()?;
+        match id.as_slice() {
+            [permanent, changing] => Some((
+                permanent.resolve(&self.doc).as_str().ok()?.to_vec(),
+                changing.resolve(&self.doc).as_str().ok()?.to_vec(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Gets the date the field's widget was last modified, from its "M" entry. This is the raw
+    /// PDF date string, e.g. `D:20190101120000Z`.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_mod_date(&self, n: usize) -> Option<String> {
+        self.get_widget(n)
+            .get(b"M")
+            .ok()?
+            .resolve(&self.doc)
+            .as_str()
+            .ok()
+            .map(decode_text)
+    }
+
+    /// When enabled, every value setter also stamps the modified field's widget with the current
+    /// time in its "M" entry, so the change can be seen with `get_field_mod_date`. Disabled by
+    /// default.
+    pub fn set_track_field_modifications(&mut self, track: bool) {
+        self.track_field_modifications = track;
+    }
+
+    /// When enabled, saving the form gives the document a new version identifier: the second part
+    /// of the trailer's "ID" is replaced with a random one, while the permanent first part is
+    /// kept. Documents without an identifier get a new one. Disabled by default. See
+    /// `document_id`.
+    pub fn set_regenerate_id_on_save(&mut self, regenerate: bool) {
+        self.regenerate_id_on_save = regenerate;
+    }
+
+    /// When enabled, `set_text` regenerates the appearance of text fields that already have one
+    /// with the font, font size and color of the existing appearance, instead of removing it and
+    /// leaving viewers to fall back to the default appearance ("DA"). This keeps the look the
+    /// form's author chose. Disabled by default.
+    pub fn set_preserve_appearance_font(&mut self, preserve: bool) {
+        self.preserve_appearance_font = preserve;
+    }
+
+    /// When enabled, saving the form also attaches the values of its fields to the document, as
+    /// a JSON object from export names (see `csv_headers`) to values written as in
+    /// `export_csv_row`, in an embedded file named "form-data.json". The data then travels with
+    /// the document, for auditing or filling another copy. The attachment is replaced on every
+    /// save. Disabled by default.
+    pub fn set_embed_values_on_save(&mut self, embed: bool) {
+        self.embed_values_on_save = embed;
+    }
+
+    /// When enabled, `set_text` removes whitespace from the start and end of the text before
+    /// storing it. Only the ends of the whole text are trimmed, so the lines of multiline text
+    /// keep their indentation. Disabled by default.
+    pub fn set_trim_text(&mut self, trim: bool) {
+        self.trim_text = trim;
+    }
+
+    /// When enabled, every value setter also removes the XFA form from hybrid forms (see
+    /// `form_kind`), so viewers show the AcroForm fields with the new values instead of the
+    /// outdated XFA form. Disabled by default.
+    pub fn set_clear_xfa_on_change(&mut self, clear: bool) {
+        self.clear_xfa_on_change = clear;
+    }
+
+    /// Gets the rectangle of the field's widget on its page ("Rect"), as `[left, bottom, right,
+    /// top]` in default user space, where the origin is the bottom left corner of the page. For
+    /// fields with several widgets, this is the rectangle of the first.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_rect(&self, n: usize) -> Option<[f64; 4]> {
+        // Fields kept apart from their widgets have no rectangle of their own, so use the first
+        // widget in "Kids" that has one
+        self.get_widget_ids(n).into_iter().find_map(|widget_id| {
+            let widget = self.doc.objects.get(&widget_id)?.as_dict().ok()?;
+            as_rect(widget.get(b"Rect").ok()?.resolve(&self.doc))
+        })
+    }
+
+    /// Gets the smallest rectangle enclosing all of the field's widgets on the page of its first
+    /// widget, as `[left, bottom, right, top]` like `get_field_rect`, e.g. to highlight a field
+    /// made of several widgets. Widgets on other pages are left out.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn field_union_rect(&self, n: usize) -> Option<[f64; 4]> {
+        let rects: Vec<(Option<ObjectId>, [f64; 4])> = self
+            .get_widget_ids(n)
+            .into_iter()
+            .filter_map(|widget_id| {
+                let widget = self.doc.objects.get(&widget_id)?.as_dict().ok()?;
+                let rect = as_rect(widget.get(b"Rect").ok()?.resolve(&self.doc))?;
+                Some((self.get_widget_page(widget_id), rect))
+            })
+            .collect();
+        let page = rects.first()?.0;
+        rects
+            .into_iter()
+            .filter(|&(widget_page, _)| widget_page == page)
+            .map(|(_, rect)| rect)
+            .fold(None, |union: Option<[f64; 4]>, rect| {
+                Some(match union {
+                    Some(union) => [
+                        union[0].min(rect[0]),
+                        union[1].min(rect[1]),
+                        union[2].max(rect[2]),
+                        union[3].max(rect[3]),
+                    ],
+                    None => rect,
+                })
+            })
+    }
+
+    /// Gets the width and height the field's widget is displayed with, which are those of its
+    /// rectangle swapped if the widget's contents are rotated by 90 or 270 degrees ("R" in its
+    /// appearance characteristics)
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_display_size(&self, n: usize) -> Option<(f64, f64)> {
+        let rect = self.get_field_rect(n)?;
+        let (width, height) = (rect[2] - rect[0], rect[3] - rect[1]);
+        let rotation = self
+            .get_widget(n)
+            .get_deref(b"MK", &self.doc)
+            .and_then(Object::as_dict)
+            .and_then(|mk| mk.get(b"R"))
+            .and_then(Object::as_i64)
+            .unwrap_or(0);
+        if rotation.rem_euclid(180) == 90 {
+            Some((height, width))
+        } else {
+            Some((width, height))
+        }
+    }
+
+    /// Gets the rectangle of the field's widget with the origin moved to the top left corner of
+    /// its page's media box and the y axis pointing down, as most UI toolkits use, as `[left,
+    /// top, right, bottom]`
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_rect_topleft(&self, n: usize) -> Option<[f64; 4]> {
+        let rect = self.get_field_rect(n)?;
+        let media_box = self.get_page_media_box(self.get_field_page(n)?)?;
+        Some([
+            rect[0] - media_box[0],
+            media_box[3] - rect[3],
+            rect[2] - media_box[0],
+            media_box[3] - rect[1],
+        ])
+    }
+
+    /// Gets the index (counting from 0) of the page the field's widget is placed on
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_page(&self, n: usize) -> Option<usize> {
+        let page_id = self.get_widget_page(self.get_widget_id(n))?;
+        self.doc.get_pages().values().position(|&id| id == page_id)
+    }
+
+    /// Gets the media box of the page at index `page` (counting from 0), as `[left, bottom,
+    /// right, top]`. It defines the coordinate space of the page, which field rectangles are given
+    /// in. The box may be inherited from the page tree.
+    pub fn get_page_media_box(&self, page: usize) -> Option<[f64; 4]> {
+        let page_id = *self.doc.get_pages().values().nth(page)?;
+        let media_box = self.get_inherited_page_attribute(page_id, b"MediaBox")?;
+        as_rect(media_box.resolve(&self.doc))
+    }
+
+    /// Gets the object ids of the annotations on the page at index `page` (counting from 0)
+    /// that aren't form widgets, such as text notes, stamps and links. Annotations written
+    /// directly into the page's "Annots" rather than as objects of their own have no id and are
+    /// left out.
+    pub fn non_widget_annotations(&self, page: usize) -> Vec<ObjectId> {
+        let page_id = match self.doc.get_pages().values().nth(page) {
+            Some(&page_id) => page_id,
+            None => return Vec::new(),
+        };
+        let annots = self
+            .doc
+            .objects
+            .get(&page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"Annots").ok())
+            .and_then(|annots| annots.resolve(&self.doc).as_array().ok());
+        annots
+            .into_iter()
+            .flatten()
+            .filter_map(|annot| annot.as_reference().ok())
+            .filter(|annot_id| {
+                let subtype = self
+                    .doc
+                    .objects
+                    .get(annot_id)
+                    .and_then(|annot| annot.as_dict().ok())
+                    .and_then(|annot| annot.get(b"Subtype").and_then(Object::as_name).ok());
+                subtype != Some(b"Widget")
+            })
+            .collect()
+    }
+
+    /// Gets the tab order of the page at index `page` (counting from 0) from its "Tabs" entry:
+    /// 'R' for row order, 'C' for column order or 'S' for structure order, or in PDF 2.0 'A'
+    /// for annotation array order or 'W' for widget order. Returns `None` if the page doesn't
+    /// specify one.
+    pub fn page_tabs(&self, page: usize) -> Option<char> {
+        let page_id = *self.doc.get_pages().values().nth(page)?;
+        let tabs = self
+            .doc
+            .objects
+            .get(&page_id)?
+            .as_dict()
+            .ok()?
+            .get(b"Tabs")
+            .ok()?
+            .resolve(&self.doc)
+            .as_name()
+            .ok()?;
+        match *tabs {
+            [tab] => Some(tab as char),
+            _ => None,
+        }
+    }
+
+    /// Gets the indices of the fields with a widget on the page at index `page` (counting from
+    /// 0) whose rectangle intersects `region`, given as `[left, bottom, right, top]` like
+    /// `get_field_rect`. Rectangles touching the region count, so a region of zero size finds
+    /// the fields under a point.
+    pub fn fields_in_region(&self, page: usize, region: [f64; 4]) -> Vec<usize> {
+        let page_id = match self.doc.get_pages().values().nth(page) {
+            Some(&page_id) => page_id,
+            None => return Vec::new(),
+        };
+        let [left, bottom, right, top] = region;
+        (0..self.len())
+            .filter(|&n| {
+                self.get_widget_ids(n).into_iter().any(|widget_id| {
+                    let rect = self
+                        .doc
+                        .objects
+                        .get(&widget_id)
+                        .and_then(|widget| widget.as_dict().ok())
+                        .and_then(|widget| widget.get(b"Rect").ok())
+                        .and_then(|rect| as_rect(rect.resolve(&self.doc)));
+                    match rect {
+                        Some(rect) => {
+                            self.get_widget_page(widget_id) == Some(page_id)
+                                && rect[0] <= right
+                                && left <= rect[2]
+                                && rect[1] <= top
+                                && bottom <= rect[3]
+                        }
+                        None => false,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the label of the page the field is placed on, as defined by the document's
+    /// "PageLabels", e.g. "ii" or "A-1". Returns `None` if the document doesn't label its pages.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_page_label(&self, n: usize) -> Option<String> {
+        let page = self.get_field_page(n)?;
+        let labels = self
+            .doc
+            .catalog()
+            .ok()?
+            .get(b"PageLabels")
+            .ok()?
+            .resolve(&self.doc);
+        let mut ranges = Vec::new();
+        self.collect_number_tree(labels, &mut ranges);
+        // The label range in effect is the one starting closest before the page
+        let (start, style) = ranges
+            .into_iter()
+            .filter(|&(start, _)| start >= 0 && start as usize <= page)
+            .max_by_key(|&(start, _)| start)?;
+        let style = style.resolve(&self.doc).as_dict().ok()?;
+        let mut label = style
+            .get(b"P")
+            .and_then(Object::as_str)
+            .map(decode_text)
+            .unwrap_or_default();
+        let first = style.get(b"St").and_then(Object::as_i64).unwrap_or(1);
+        let number = first + (page as i64 - start);
+        match style.get(b"S").and_then(Object::as_name) {
+            Ok(b"D") => label.push_str(&number.to_string()),
+            Ok(b"R") => label.push_str(&to_roman(number).to_uppercase()),
+            Ok(b"r") => label.push_str(&to_roman(number)),
+            Ok(b"A") => label.push_str(&to_letters(number).to_uppercase()),
+            Ok(b"a") => label.push_str(&to_letters(number)),
+            _ => (),
+        }
+        Some(label)
+    }
+
+    /// Returns true if the file the form was loaded from goes on past the end of the byte range
+    /// ("ByteRange") covered by its latest signature, i.e. if incremental updates were appended
+    /// after it was signed. The signature itself isn't verified. Returns false if the form
+    /// has no signed signature fields, or wasn't loaded from a file.
+    pub fn modified_after_signature(&self) -> bool {
+        let source_len = match self.source_len {
+            Some(source_len) => source_len,
+            None => return false,
+        };
+        let signed_len = (0..self.len())
+            .filter(|&n| {
+                self.get_inheritable(n, b"FT")
+                    .and_then(|ft| ft.as_name().ok())
+                    == Some(b"Sig")
+            })
+            .filter_map(|n| {
+                let range = self
+                    .get_inheritable(n, b"V")?
+                    .resolve(&self.doc)
+                    .as_dict()
+                    .ok()?
+                    .get(b"ByteRange")
+                    .and_then(Object::as_array)
+                    .ok()?;
+                match range.iter().map(Object::as_i64).collect::<Vec<_>>()[..] {
+                    [Ok(_), Ok(_), Ok(start), Ok(len)] => Some((start + len) as usize),
+                    _ => None,
+                }
+            })
+            .max();
+        match signed_len {
+            Some(signed_len) => source_len > signed_len,
+            None => false,
+        }
+    }
+
+    /// Gets the indices of the fields that become read-only once the signature field at index
+    /// `sig_index` is signed, according to its field lock dictionary ("Lock"). A lock on named
+    /// fields also covers their descendants. Returns an empty list if the field is not a signature
+    /// field or locks nothing.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn fields_locked_by_signature(&self, sig_index: usize) -> Vec<usize> {
+        if self
+            .get_inheritable(sig_index, b"FT")
+            .and_then(|ft| ft.as_name().ok())
+            != Some(b"Sig")
+        {
+            return Vec::new();
+        }
+        let lock = match self
+            .get_inheritable(sig_index, b"Lock")
+            .and_then(|lock| lock.resolve(&self.doc).as_dict().ok())
+        {
+            Some(lock) => lock,
+            None => return Vec::new(),
+        };
+        let names: Vec<String> = lock
+            .get(b"Fields")
+            .ok()
+            .and_then(|fields| fields.resolve(&self.doc).as_array().ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.resolve(&self.doc).as_str().ok())
+            .map(decode_text)
+            .collect();
+        let is_listed = |i: usize| match self.get_full_name(i) {
+            Some(full_name) => names
+                .iter()
+                .any(|name| full_name == *name || full_name.starts_with(&format!("{}.", name))),
+            None => false,
+        };
+        // `None` locks every field, otherwise fields are locked if being listed matches this
+        let listed = match lock.get(b"Action").and_then(Object::as_name) {
+            Ok(b"All") => None,
+            Ok(b"Include") => Some(true),
+            Ok(b"Exclude") => Some(false),
+            _ => return Vec::new(),
+        };
+        (0..self.len())
+            .filter(|&i| i != sig_index)
+            .filter(|&i| match listed {
+                Some(listed) => is_listed(i) == listed,
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Gets the name of the optional content group (layer) the field's widget belongs to, from
+    /// its "OC" entry. When visibility depends on a membership dictionary over several groups,
+    /// the first group's name is returned.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_field_layer(&self, n: usize) -> Option<String> {
+        let oc = self
+            .get_widget(n)
+            .get(b"OC")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()?;
+        let group = match oc.get(b"Type").and_then(Object::as_name) {
+            Ok(b"OCMD") => {
+                let groups = oc.get(b"OCGs").ok()?.resolve(&self.doc);
+                match groups.as_array() {
+                    Ok(groups) => groups.first()?.resolve(&self.doc),
+                    Err(_) => groups,
+                }
+                .as_dict()
+                .ok()?
+            }
+            _ => oc,
+        };
+        group
+            .get(b"Name")
+            .ok()?
+            .resolve(&self.doc)
+            .as_str()
+            .ok()
+            .map(decode_text)
+    }
+
+    /// Gets the accessible name of the field: the alternate text ("Alt") of the structure
+    /// element its widget is tagged with, found through the widget's "StructParent" in the
+    /// structure tree's parent tree, or else the field's tooltip ("TU")
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_accessible_name(&self, n: usize) -> Option<String> {
+        self.get_structure_alt(n).or_else(|| {
+            self.doc
+                .objects
+                .get(&self.form_ids[n])?
+                .as_dict()
+                .ok()?
+                .get(b"TU")
+                .ok()?
+                .resolve(&self.doc)
+                .as_str()
+                .ok()
+                .map(decode_text)
+        })
+    }
+
+    /// Gets how the field's value ("V") is encoded, to diagnose values that display wrongly.
+    /// Values written as UTF-16BE are reported as such, whether the string is literal or
+    /// hexadecimal. Returns `None` if the value isn't a string.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn value_encoding(&self, n: usize) -> Option<StringEncoding> {
+        match *self.get_inheritable(n, b"V")?.resolve(&self.doc) {
+            Object::String(ref text, _) if text.starts_with(&[0xFE, 0xFF]) => {
+                Some(StringEncoding::Utf16Be)
+            }
+            Object::String(_, StringFormat::Hexadecimal) => Some(StringEncoding::Hex),
+            Object::String(_, StringFormat::Literal) => Some(StringEncoding::PdfDocEncoding),
+            _ => None,
+        }
+    }
+
+    /// Gets the appearance state ("AS") of the field's widget, the name of the appearance
+    /// viewers draw it with, e.g. "Off" or the on state of a check box. For fields with several
+    /// widgets, such as radio buttons, this is the state of the first; see `selected_widget`.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_appearance_state(&self, n: usize) -> Option<String> {
+        self.get_widget(n)
+            .get(b"AS")
+            .and_then(Object::as_name)
+            .ok()
+            .map(|state| String::from_utf8_lossy(state).into_owned())
+    }
+
+    /// Gets the selected options of a list box or combo box, reconciling the selected indices
+    /// ("I") with the value ("V"): when every index refers to an option, the options at those
+    /// indices are the selection, even if the value disagrees; otherwise the value is. This is
+    /// the selection `get_state` reports. Returns an empty list for other fields.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn resolved_selection(&self, n: usize) -> Vec<String> {
+        match self.get_type(n) {
+            FieldType::ListBox | FieldType::ComboBox => self.get_selection(n),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Gets the number of entries in the field's options ("Opt") without decoding them, or 0 if
+    /// it has none
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn option_count(&self, n: usize) -> usize {
+        self.get_inheritable(n, b"Opt")
+            .and_then(|options| options.as_array().ok())
+            .map(Vec::len)
+            .unwrap_or(0)
+    }
+
+    /// Gets the positions of the options selected by the default value ("DV") of a list box or
+    /// combo box, in the order of the default value. Values that aren't the export value of an
+    /// option are skipped. Returns an empty list for other fields. See `set_default_combo_index`.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn default_choice_indices(&self, n: usize) -> Vec<usize> {
+        match self.get_type(n) {
+            FieldType::ListBox | FieldType::ComboBox => (),
+            _ => return Vec::new(),
+        }
+        let defaults: Vec<String> = match self.get_inheritable(n, b"DV") {
+            Some(Object::Array(values)) => values
+                .iter()
+                .filter_map(|value| value.resolve(&self.doc).as_str().ok())
+                .map(decode_text)
+                .collect(),
+            Some(Object::String(value, _)) => vec![decode_text(value)],
+            _ => return Vec::new(),
+        };
+        let options = self.get_options(n);
+        defaults
+            .iter()
+            .filter_map(|default| options.iter().position(|option| option == default))
+            .collect()
+    }
+
+    /// Gets the dash pattern of the border of the field's widget, from its border style ("BS"),
+    /// as the lengths of alternating dashes and gaps, e.g. `[3.0]` for 3 on and 3 off. Returns
+    /// `None` if the border isn't dashed.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn get_border_dash(&self, n: usize) -> Option<Vec<f64>> {
+        let border_style = self
+            .get_widget(n)
+            .get(b"BS")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()?;
+        if border_style.get(b"S").and_then(Object::as_name).ok() != Some(b"D") {
+            return None;
+        }
+        match border_style.get(b"D") {
+            Ok(dash) => Some(
+                dash.resolve(&self.doc)
+                    .as_array()
+                    .ok()?
+                    .iter()
+                    .filter_map(as_number)
+                    .collect(),
+            ),
+            // The default dash pattern
+            Err(_) => Some(vec![3.0]),
+        }
+    }
+
+    /// Reads the value of a text or combo box field as a date, laid out by the pattern its
+    /// format script passes to `AFDate_FormatEx` or `AFDate_Format` (or, if it has no format
+    /// script, its keystroke script). Returns None if there is no such script, or if the value
+    /// doesn't parse as a complete date.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    #[cfg(feature = "chrono")]
+    pub fn get_date(&self, n: usize) -> Option<chrono::NaiveDate> {
+        let pattern = self
+            .get_script(n, b"F")
+            .and_then(|script| scripts::date_format(&script))
+            .or_else(|| {
+                self.get_script(n, b"K")
+                    .and_then(|script| scripts::date_format(&script))
+            })?;
+        let text = match self.get_state(n) {
+            FieldState::Text { text } => text,
+            FieldState::ComboBox { selected, .. } => selected.into_iter().next()?,
+            _ => return None,
+        };
+        let (year, month, day) = scripts::parse_date(&text, &pattern)?;
+        chrono::NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    /// Returns true if the field is a comb field whose value has more characters than it has
+    /// cells ("MaxLen"). `set_text` refuses such values, so this points to a value written by
+    /// another tool or a damaged field, which viewers will cut off or lay out wrongly.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn comb_overflow(&self, n: usize) -> bool {
+        let max_len = match self.get_comb_max_len(n) {
+            Some(max_len) if self.get_type(n) == FieldType::Text => max_len,
+            _ => return false,
+        };
+        match self.get_state(n) {
+            FieldState::Text { text } => text.chars().count() > max_len,
+            _ => false,
+        }
+    }
+
+    /// Checks whether the field's keystroke or format script would accept `s` as its value. The
+    /// scripts aren't run; calls to the number, percentage and special format functions of
+    /// Acrobat (`AFNumber_Keystroke`, `AFPercent_Keystroke`, `AFSpecial_Keystroke` and
+    /// `AFSpecial_KeystrokeEx`, and their format versions) are recognized instead. Values of
+    /// fields without such a script are always accepted.
+    ///
+    /// # Panics
+    /// This function will panic if the index is greater than the number of fields
+    pub fn value_matches_format(&self, n: usize, s: &str) -> bool {
+        [&b"K"[..], b"F"]
+            .iter()
+            .filter_map(|trigger| self.get_script(n, trigger))
+            .find_map(|script| scripts::accepts(&script, s))
+            .unwrap_or(true)
+    }
+
+    /// If the field at index `n` is a text field, fills in that field with the text `s`.
+    /// If it is not a text field, returns ValueError
+    ///
+    /// Comb fields lay their text out in exactly "MaxLen" cells, so text with more characters than
+    /// that is rejected with `ValueError::TextTooLong`.
+    ///
+    /// Surrounding whitespace is removed first if `set_trim_text` is enabled.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_text(&mut self, n: usize, s: String) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Text => {
+                let s = if self.trim_text {
+                    s.trim().to_owned()
+                } else {
+                    s
+                };
+                if let Some(max_len) = self.get_comb_max_len(n) {
+                    if s.chars().count() > max_len {
+                        return Err(ValueError::TextTooLong);
+                    }
+                }
+                let existing = if self.preserve_appearance_font {
+                    self.get_appearance_text_settings(n)
+                } else {
+                    None
+                };
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set("V", Object::String(encode_text(&s), StringFormat::Literal));
+                field.remove(b"AP");
+                if let Some((da, font)) = existing {
+                    self.write_text_appearance(n, &da, font);
+                }
+                self.touch_field(n);
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Fills in the text field at index `n` with the text `s` like `set_text`, then regenerates
+    /// the normal appearance of that field alone like `generate_appearance`, so that editors
+    /// updating one field at a time show the new value right away
+    ///
+    /// If the appearance can't be generated, the text stays set and the error
+    /// (`ValueError::MissingDefaultAppearance`) is returned.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_text_and_render(&mut self, n: usize, s: String) -> Result<(), ValueError> {
+        self.set_text(n, s)?;
+        self.generate_appearance(n)
+    }
+
+    /// If the field at index `n` is a check box, checks or unchecks it. A checked box is set to
+    /// the on state of its normal appearance ("AP"), or "On" if that has none.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_check_box(&mut self, n: usize, is_checked: bool) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::CheckBox => {
+                let state = if is_checked {
+                    self.get_radio_options(n)
+                        .into_iter()
+                        .next()
+                        .unwrap_or_else(|| "On".to_owned())
+                } else {
+                    "Off".to_owned()
+                };
+                // Each widget shows its own on state, in case they don't all use the same name
+                let widgets: Vec<(ObjectId, String)> = self
+                    .get_widget_ids(n)
+                    .into_iter()
+                    .map(|id| {
+                        let widget_state = match self.get_widget_states(id).into_iter().next() {
+                            Some(on_state) if is_checked => on_state,
+                            _ => state.clone(),
+                        };
+                        (id, widget_state)
+                    })
+                    .collect();
+                for (widget_id, widget_state) in widgets {
+                    let widget = self
+                        .doc
+                        .objects
+                        .get_mut(&widget_id)
+                        .unwrap()
+                        .as_dict_mut()
+                        .unwrap();
+                    widget.set("AS", Object::Name(widget_state.into_bytes()));
+                }
+                let state = Object::Name(state.into_bytes());
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set("V", state);
+                self.touch_field(n);
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Sets the appearance state ("AS") of the field's first widget directly, without changing
+    /// its value. The state, "Off" included, must be one of the widget's normal appearances ("N"
+    /// in "AP"); otherwise returns `ValueError::InvalidSelection`. This is meant for buttons whose
+    /// appearance the typed setters get wrong.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_appearance_state(&mut self, n: usize, state: &str) -> Result<(), ValueError> {
+        let widget_id = self.get_widget_id(n);
+        let has_state = self
+            .get_normal_appearances(widget_id)
+            .map(|states| states.has(state.as_bytes()))
+            .unwrap_or(false);
+        if !has_state {
+            return Err(ValueError::InvalidSelection);
+        }
+        if let Some(&mut Object::Dictionary(ref mut widget)) = self.doc.objects.get_mut(&widget_id)
+        {
+            widget.set("AS", Object::Name(state.as_bytes().to_vec()));
+        }
+        self.touch_field(n);
+        Ok(())
+    }
+
+    /// If the field at index `n` is a radio button group, selects the option `choice`, which
+    /// must be one of the on states of its buttons
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_radio(&mut self, n: usize, choice: String) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Radio => {
+                if !self.get_radio_options(n).contains(&choice) {
+                    return Err(ValueError::InvalidSelection);
+                }
+                let widgets: Vec<(ObjectId, bool)> = self
+                    .get_widget_ids(n)
+                    .into_iter()
+                    .map(|id| (id, self.get_widget_states(id).contains(&choice)))
+                    .collect();
+                let state = Object::Name(choice.into_bytes());
+                for (widget_id, is_selected) in widgets {
+                    let widget = self
+                        .doc
+                        .objects
+                        .get_mut(&widget_id)
+                        .unwrap()
+                        .as_dict_mut()
+                        .unwrap();
+                    if is_selected {
+                        widget.set("AS", state.clone());
+                    } else {
+                        widget.set("AS", Object::Name(b"Off".to_vec()));
+                    }
+                }
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set("V", state);
+                self.touch_field(n);
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` is a radio button group, selects the option with the label
+    /// `label`, as given by `radio_option_pairs`. The option's export value is stored as the
+    /// field's value.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_radio_by_label(&mut self, n: usize, label: &str) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Radio => {
+                let export = self
+                    .radio_option_pairs(n)
+                    .into_iter()
+                    .find(|(_, option_label)| option_label == label)
+                    .map(|(export, _)| export);
+                match export {
+                    Some(export) => self.set_radio(n, export),
+                    None => Err(ValueError::InvalidSelection),
+                }
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` is a list box, selects the options in `choices`, which must all
+    /// be among its options. See `set_list_box_indices`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_list_box(&mut self, n: usize, choices: Vec<String>) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ListBox => {
+                let options = self.get_options(n);
+                let mut indices = Vec::with_capacity(choices.len());
+                for choice in &choices {
+                    match options.iter().position(|option| option == choice) {
+                        Some(index) => indices.push(index),
+                        None => return Err(ValueError::InvalidSelection),
+                    }
+                }
+                self.set_list_box_indices(n, &indices)
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` is a combo box, selects `choice`. Unless the combo box is
+    /// editable, the choice must be one of its options.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_combo_box(&mut self, n: usize, choice: String) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ComboBox => {
+                let index = self
+                    .get_options(n)
+                    .iter()
+                    .position(|option| *option == choice);
+                if index.is_none() && !self.get_choice_flags(n).contains(ChoiceFlags::EDIT) {
+                    return Err(ValueError::InvalidSelection);
+                }
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set(
+                    "V",
+                    Object::String(encode_text(&choice), StringFormat::Literal),
+                );
+                match index {
+                    Some(index) => {
+                        field.set("I", Object::Array(vec![Object::Integer(index as i64)]))
+                    }
+                    None => {
+                        field.remove(b"I");
+                    }
+                }
+                field.remove(b"AP");
+                self.touch_field(n);
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Fills the field at index `n` with `value` using the setter for its type. Returns
+    /// `ValueError::TypeMismatch` if the value doesn't match the type of the field.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_value(&mut self, n: usize, value: FieldValue) -> Result<(), ValueError> {
+        match value {
+            FieldValue::Text(text) => self.set_text(n, text),
+            FieldValue::CheckBox(is_checked) => self.set_check_box(n, is_checked),
+            FieldValue::Radio(choice) => self.set_radio(n, choice),
+            FieldValue::ListBox(choices) => self.set_list_box(n, choices),
+            FieldValue::ComboBox(choice) => self.set_combo_box(n, choice),
+        }
+    }
+
+    /// Fills every field with the full name `full_name` with `value`, for forms that repeat a
+    /// logical field as several fields, e.g. a name shown on every page. Returns
+    /// `ValueError::NoSuchField` if no field has the name, or the first error a setter returns.
+    pub fn set_group_value(
+        &mut self,
+        full_name: &str,
+        value: FieldValue,
+    ) -> Result<(), ValueError> {
+        let indices = self.indices_by_name(full_name);
+        if indices.is_empty() {
+            return Err(ValueError::NoSuchField);
+        }
+        for n in indices {
+            self.set_value(n, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Parses a CSV line and fills every field whose export name is in `headers` with the value
+    /// in the same column, in the format written by `export_csv_row`: check boxes are unchecked
+    /// by "Off", "false", "no", "0" or a blank value and checked by anything else, and list box
+    /// selections are separated by semicolons. Quoted values may contain commas and doubled
+    /// quotes. Headers no field has are skipped, and so are values without a header. Returns the
+    /// first error a setter returns.
+    pub fn apply_csv_row(&mut self, headers: &[&str], row: &str) -> Result<(), ValueError> {
+        for (header, value) in headers.iter().zip(parse_csv_row(row)) {
+            for n in self.indices_by_export_name(header) {
+                if let Some(value) = self.parse_value_text(n, &value) {
+                    self.set_value(n, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills the form from "name=value" lines, one field per line, with the values written as
+    /// in `apply_csv_row`. Whitespace around names and values is ignored, and so are blank
+    /// lines, lines starting with "#" and lines without "=". Names no field has are skipped.
+    /// Returns the first error a setter returns.
+    pub fn apply_lines(&mut self, input: &str) -> Result<(), ValueError> {
+        for line in input.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => continue,
+            };
+            for n in self.indices_by_name(name) {
+                if let Some(value) = self.parse_value_text(n, value) {
+                    self.set_value(n, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the mapping name ("TM") of the field of the given index. See `get_mapping_name`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_mapping_name(&mut self, n: usize, name: &str) {
+        let field = self
+            .doc
+            .objects
+            .get_mut(&self.form_ids[n])
+            .unwrap()
+            .as_dict_mut()
+            .unwrap();
+        field.set(
+            "TM",
+            Object::String(encode_text(name), StringFormat::Literal),
+        );
+    }
+
+    /// Makes clicking the field's widget open `uri`, by setting a URI action as its activation
+    /// action ("A"). Any previous activation action is replaced. The action can be read back with
+    /// `get_action`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_field_link(&mut self, n: usize, uri: &str) {
+        let mut action = Dictionary::new();
+        action.set("Type", Object::Name(b"Action".to_vec()));
+        action.set("S", Object::Name(b"URI".to_vec()));
+        // URIs are 7-bit ASCII, so they're stored as is rather than as text strings
+        action.set(
+            "URI",
+            Object::String(uri.as_bytes().to_vec(), StringFormat::Literal),
+        );
+        let widget_id = self.get_widget_id(n);
+        let widget = self
+            .doc
+            .objects
+            .get_mut(&widget_id)
+            .unwrap()
+            .as_dict_mut()
+            .unwrap();
+        widget.set("A", Object::Dictionary(action));
+    }
+
+    /// If the field at index `n` is a list box, selects the options at the positions in `indices`.
+    /// Both the selected values ("V") and the selected indices ("I") are written.
+    ///
+    /// Returns `ValueError::InvalidSelection` if an index is out of range, and
+    /// `ValueError::TooManySelected` if several are given but the list box is not multiselect.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_list_box_indices(&mut self, n: usize, indices: &[usize]) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ListBox => {
+                let options = self.get_options(n);
+                if indices.iter().any(|&i| i >= options.len()) {
+                    return Err(ValueError::InvalidSelection);
+                }
+                // "I" must be sorted in ascending order, and an index given twice selects one
+                // option
+                let mut indices = indices.to_vec();
+                indices.sort();
+                indices.dedup();
+                if indices.len() > 1 && !self.get_choice_flags(n).contains(ChoiceFlags::MULTISELECT)
+                {
+                    return Err(ValueError::TooManySelected);
+                }
+                let mut selected: Vec<Object> = indices
+                    .iter()
+                    .map(|&i| Object::String(encode_text(&options[i]), StringFormat::Literal))
+                    .collect();
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                match selected.len() {
+                    0 => {
+                        field.remove(b"V");
+                    }
+                    1 => field.set("V", selected.remove(0)),
+                    _ => field.set("V", Object::Array(selected)),
+                }
+                field.set(
+                    "I",
+                    Object::Array(indices.iter().map(|&i| Object::Integer(i as i64)).collect()),
+                );
+                field.remove(b"AP");
+                self.touch_field(n);
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Sets the default justification ("Q") for fields that don't specify one
+    pub fn set_form_quadding(&mut self, quadding: Quadding) {
+        if let Some(acroform) = self.get_acroform_mut() {
+            acroform.set("Q", Object::Integer(quadding_to_i64(quadding)));
+        }
+    }
+
+    /// If the field at index `n` is a text field, sets its default style string ("DS"). See
+    /// `get_default_style`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_default_style(&mut self, n: usize, style: &str) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Text => {
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set(
+                    "DS",
+                    Object::String(encode_text(style), StringFormat::Literal),
+                );
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` holds text (a text, list box or combo box field), sets the color
+    /// its text is shown with in its default appearance ("DA"). Gray, RGB and CMYK colors are
+    /// supported. The field gets a default appearance of its own if it inherited one.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_text_color(&mut self, n: usize, color: Color) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Text | FieldType::ListBox | FieldType::ComboBox => {
+                let da = match self.get_default_appearance(n) {
+                    Some(da) => appearance::replace_color(&da, color),
+                    None => return Err(ValueError::MissingDefaultAppearance),
+                };
+                let field = self
+                    .doc
+                    .objects
+                    .get_mut(&self.form_ids[n])
+                    .unwrap()
+                    .as_dict_mut()
+                    .unwrap();
+                field.set(
+                    "DA",
+                    Object::String(encode_text(&da), StringFormat::Literal),
+                );
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Makes the border of the field's widgets dashed with the pattern `dash`, the lengths of
+    /// alternating dashes and gaps, by setting the style and dash array of their border style
+    /// ("BS"). An empty pattern makes the border solid. See `get_border_dash`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_border_dash(&mut self, n: usize, dash: &[f64]) {
+        for widget_id in self.get_widget_ids(n) {
+            let mut border_style = match self.doc.objects.get(&widget_id) {
+                Some(Object::Dictionary(widget)) => widget
+                    .get(b"BS")
+                    .and_then(|bs| bs.resolve(&self.doc).as_dict())
+                    .cloned()
+                    .unwrap_or_default(),
+                _ => continue,
+            };
+            if dash.is_empty() {
+                border_style.set("S", Object::Name(b"S".to_vec()));
+                border_style.remove(b"D");
+            } else {
+                border_style.set("S", Object::Name(b"D".to_vec()));
+                border_style.set(
+                    "D",
+                    Object::Array(dash.iter().map(|&length| Object::Real(length)).collect()),
+                );
+            }
+            if let Some(&mut Object::Dictionary(ref mut widget)) =
+                self.doc.objects.get_mut(&widget_id)
+            {
+                widget.set("BS", Object::Dictionary(border_style));
+            }
+        }
+    }
+
+    /// Gives the field at index `n` a default appearance ("DA") if neither it nor its parents
+    /// have one: the form's default appearance if it has one, and `fallback`, e.g.
+    /// `/Helv 0 Tf 0 g`, otherwise. Appearances can then be generated for the field.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn ensure_da(&mut self, n: usize, fallback: &str) {
+        if self.get_inheritable(n, b"DA").is_some() {
+            return;
+        }
+        let da = self
+            .get_acroform()
+            .and_then(|acroform| acroform.get(b"DA").ok())
+            .and_then(|da| da.resolve(&self.doc).as_str().ok())
+            .map(<[u8]>::to_vec)
+            .unwrap_or_else(|| encode_text(fallback));
+        let field = self
+            .doc
+            .objects
+            .get_mut(&self.form_ids[n])
+            .unwrap()
+            .as_dict_mut()
+            .unwrap();
+        field.set("DA", Object::String(da, StringFormat::Literal));
+    }
+
+    /// If the field at index `n` is a combo box, sets its default value ("DV") to the export value
+    /// of the option at position `index`. The default is what the field returns to when the form
+    /// is reset.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_default_combo_index(&mut self, n: usize, index: usize) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ComboBox => {
+                let option = match self.get_options(n).into_iter().nth(index) {
+                    Some(option) => option,
+                    None => return Err(ValueError::InvalidSelection),
+                };
+                let field = self
+                    .doc
+                    .objects
                     .get_mut(&self.form_ids[n])
                     .unwrap()
                     .as_dict_mut()
                     .unwrap();
-                field.set("V", Object::String(s.into_bytes(), StringFormat::Literal));
-                field.remove(b"AP");
+                field.set(
+                    "DV",
+                    Object::String(encode_text(&option), StringFormat::Literal),
+                );
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` is a choice field, turns it into a combo box (dropdown) when
+    /// `combo` is true or a list box otherwise, by toggling its Combo flag. The options and the
+    /// current selection are kept as they are. List boxes can't be edited, so turning a field
+    /// into one also clears its Edit flag.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_choice_style(&mut self, n: usize, combo: bool) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ComboBox | FieldType::ListBox => {
+                self.set_field_flag(n, ChoiceFlags::COBMO.bits(), combo);
+                if !combo {
+                    self.set_field_flag(n, ChoiceFlags::EDIT.bits(), false);
+                }
+                Ok(())
+            }
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// If the field at index `n` is a combo box, allows or disallows typing in a value that isn't
+    /// one of its options, by setting or clearing its Edit flag. See `set_combo_box`.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn set_combo_editable(&mut self, n: usize, editable: bool) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::ComboBox => {
+                self.set_field_flag(n, ChoiceFlags::EDIT.bits(), editable);
                 Ok(())
             }
-            _ => Err(ValueError::TypeMismatch),
+            _ => Err(ValueError::TypeMismatch),
+        }
+    }
+
+    /// Adds a copy of the field at index `n`, named `new_name`, with its widget placed at
+    /// `new_rect` (`[left, bottom, right, top]`) on the same page. The copy has the same parent,
+    /// so the name should not be used by any of its siblings. Only the field's first widget is
+    /// copied. Returns the index of the new field, which is added to the end of the form and can
+    /// be filled independently of the original. `get_field_page` reports the copy's page.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn duplicate_field(
+        &mut self,
+        n: usize,
+        new_name: &str,
+        new_rect: [f64; 4],
+    ) -> Result<usize, ValueError> {
+        let field_id = self.form_ids[n];
+        let widget_id = self.get_widget_id(n);
+        let page_id = match self.get_widget_page(widget_id) {
+            Some(page_id) => page_id,
+            None => return Err(ValueError::InvalidPage),
+        };
+        let mut field = self
+            .doc
+            .objects
+            .get(&field_id)
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .clone();
+        field.set(
+            "T",
+            Object::String(encode_text(new_name), StringFormat::Literal),
+        );
+        let mut widget = if widget_id == field_id {
+            field.clone()
+        } else {
+            self.get_widget(n).clone()
+        };
+        widget.set(
+            "Rect",
+            Object::Array(new_rect.iter().map(|&c| Object::Real(c)).collect()),
+        );
+        // Structure parent keys are unique to each annotation
+        widget.remove(b"StructParent");
+        // The original's "P" may be missing or wrong, and some viewers misplace widgets without
+        // it, so point it at the page the copy is added to
+        widget.set("P", Object::Reference(page_id));
+
+        let new_field_id = self.doc.new_object_id();
+        let new_widget_id = if widget_id == field_id {
+            self.doc
+                .objects
+                .insert(new_field_id, Object::Dictionary(widget));
+            new_field_id
+        } else {
+            widget.set("Parent", Object::Reference(new_field_id));
+            let new_widget_id = self.doc.add_object(widget);
+            field.set(
+                "Kids",
+                Object::Array(vec![Object::Reference(new_widget_id)]),
+            );
+            self.doc
+                .objects
+                .insert(new_field_id, Object::Dictionary(field.clone()));
+            new_widget_id
+        };
+        match field.get(b"Parent").and_then(Object::as_reference) {
+            Ok(parent_id) => self.push_kid(parent_id, new_field_id),
+            Err(_) => self.push_acroform_field(new_field_id),
+        }
+        match self.get_annots_mut(page_id) {
+            Some(annots) => annots.push(Object::Reference(new_widget_id)),
+            None => {
+                if let Some(&mut Object::Dictionary(ref mut page)) =
+                    self.doc.objects.get_mut(&page_id)
+                {
+                    page.set(
+                        "Annots",
+                        Object::Array(vec![Object::Reference(new_widget_id)]),
+                    );
+                }
+            }
+        }
+        self.form_ids.push(new_field_id);
+        Ok(self.form_ids.len() - 1)
+    }
+
+    /// Clones the page at index `page` (counting from 0) along with its annotations and appends
+    /// the copy to the end of the document. Every field with a widget on the page is duplicated
+    /// with `name_suffix` appended to its partial name, and the new fields are added to the end of
+    /// the form. Returns the index of the new page.
+    ///
+    /// The suffix should not have been used for the page before, or the new field names will
+    /// collide with the earlier copies. The copies are left out of the structure tree, as they
+    /// have no entries of their own in it.
+    ///
+    /// Returns `ValueError::InvalidPage` if the page doesn't exist or the document's page tree
+    /// can't be added to.
+    pub fn duplicate_page_with_fields(
+        &mut self,
+        page: usize,
+        name_suffix: &str,
+    ) -> Result<usize, ValueError> {
+        let page_id = match self.doc.get_pages().values().nth(page) {
+            Some(&id) => id,
+            None => return Err(ValueError::InvalidPage),
+        };
+        let pages_id = match self
+            .doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+        {
+            Ok(id) => id,
+            Err(_) => return Err(ValueError::InvalidPage),
+        };
+        let pages_has_kids = self
+            .doc
+            .get_object(pages_id)
+            .and_then(Object::as_dict)
+            .and_then(|pages| pages.get(b"Kids"))
+            .and_then(Object::as_array)
+            .is_ok();
+        if !pages_has_kids {
+            return Err(ValueError::InvalidPage);
+        }
+        let mut new_page = self
+            .doc
+            .objects
+            .get(&page_id)
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .clone();
+        // The copy hangs directly off the root of the page tree, so any attributes it inherited
+        // from intermediate nodes have to be copied onto it
+        for key in &[&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+            if !new_page.has(key) {
+                if let Some(value) = self.get_inherited_page_attribute(page_id, key) {
+                    new_page.set(key.to_vec(), value);
+                }
+            }
+        }
+        new_page.set("Parent", Object::Reference(pages_id));
+        // The structure tree's "ParentTree" maps these numbers to the original's content, so
+        // sharing them would make two pages claim the same entry
+        new_page.remove(b"StructParents");
+        let annots = new_page
+            .get(b"Annots")
+            .and_then(|annots| annots.resolve(&self.doc).as_array())
+            .cloned()
+            .unwrap_or_default();
+        let new_page_id = self.doc.new_object_id();
+
+        // Maps each duplicated field's original id to its copy, so widgets sharing a parent
+        // field end up under the same copied parent
+        let mut copied_parents: Vec<(ObjectId, ObjectId)> = Vec::new();
+        let mut new_fields = Vec::new();
+        let mut new_annots = Vec::new();
+        for annot in annots {
+            let mut widget = match annot.resolve(&self.doc).as_dict() {
+                Ok(widget) => widget.clone(),
+                Err(_) => continue,
+            };
+            widget.set("P", Object::Reference(new_page_id));
+            widget.remove(b"StructParent");
+            let is_widget = widget
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .map(|s| s == b"Widget")
+                .unwrap_or(false);
+            if !is_widget {
+                new_annots.push(Object::Reference(self.doc.add_object(widget)));
+                continue;
+            }
+            let widget_id = self.doc.new_object_id();
+            if widget.has(b"T") {
+                // The widget is merged with its field, so rename it directly
+                rename_with_suffix(&mut widget, name_suffix);
+                match widget.get(b"Parent").and_then(Object::as_reference) {
+                    Ok(parent_id) => self.push_kid(parent_id, widget_id),
+                    Err(_) => self.push_acroform_field(widget_id),
+                }
+                if widget.has(b"FT") {
+                    new_fields.push(widget_id);
+                }
+            } else if let Ok(parent_id) = widget.get(b"Parent").and_then(Object::as_reference) {
+                let copy_id = match copied_parents.iter().find(|&&(old, _)| old == parent_id) {
+                    Some(&(_, copy_id)) => copy_id,
+                    None => {
+                        let mut parent = self
+                            .doc
+                            .objects
+                            .get(&parent_id)
+                            .and_then(|p| p.as_dict().ok())
+                            .cloned()
+                            .unwrap_or_default();
+                        rename_with_suffix(&mut parent, name_suffix);
+                        parent.set("Kids", Object::Array(Vec::new()));
+                        let copy_id = self.doc.add_object(parent.clone());
+                        match parent.get(b"Parent").and_then(Object::as_reference) {
+                            Ok(grandparent_id) => self.push_kid(grandparent_id, copy_id),
+                            Err(_) => self.push_acroform_field(copy_id),
+                        }
+                        if parent.has(b"FT") {
+                            new_fields.push(copy_id);
+                        }
+                        copied_parents.push((parent_id, copy_id));
+                        copy_id
+                    }
+                };
+                widget.set("Parent", Object::Reference(copy_id));
+                self.push_kid(copy_id, widget_id);
+            }
+            self.doc
+                .objects
+                .insert(widget_id, Object::Dictionary(widget));
+            new_annots.push(Object::Reference(widget_id));
+        }
+
+        new_page.set("Annots", Object::Array(new_annots));
+        self.doc
+            .objects
+            .insert(new_page_id, Object::Dictionary(new_page));
+        // Checked above to be a dictionary with "Kids"
+        let pages = self
+            .doc
+            .objects
+            .get_mut(&pages_id)
+            .unwrap()
+            .as_dict_mut()
+            .unwrap();
+        let count = pages.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+        pages.set("Count", Object::Integer(count + 1));
+        pages
+            .get_mut(b"Kids")
+            .unwrap()
+            .as_array_mut()
+            .unwrap()
+            .push(Object::Reference(new_page_id));
+
+        self.form_ids.append(&mut new_fields);
+        Ok(self.doc.get_pages().len() - 1)
+    }
+
+    /// Turns the check boxes at `indices` into a single radio button group named `group_name`, so
+    /// that at most one of them can be on. The widgets of the check boxes become the kids of the
+    /// new radio field, and any that share an on state are given distinct ones. If several are
+    /// checked, only the first stays selected.
+    ///
+    /// The check boxes are removed from the form and the radio field is added to the end, so the
+    /// indices of the remaining fields may shift. Returns the index of the radio field.
+    pub fn group_as_radio(
+        &mut self,
+        indices: &[usize],
+        group_name: &str,
+    ) -> Result<usize, ValueError> {
+        if indices.is_empty() {
+            return Err(ValueError::InvalidSelection);
+        }
+        for &i in indices {
+            match self.get_type(i) {
+                FieldType::CheckBox => (),
+                _ => return Err(ValueError::TypeMismatch),
+            }
+        }
+        let mut indices = indices.to_vec();
+        indices.sort();
+        indices.dedup();
+
+        let parent_id = self.doc.new_object_id();
+        let mut widget_ids = Vec::new();
+        for &i in &indices {
+            let field_id = self.form_ids[i];
+            let field = self.doc.objects.get(&field_id).unwrap().as_dict().unwrap();
+            match field.get(b"Kids").and_then(Object::as_array) {
+                Ok(kids) => {
+                    widget_ids.extend(kids.iter().filter_map(|kid| kid.as_reference().ok()));
+                    self.detach_field(field_id);
+                    self.doc.objects.remove(&field_id);
+                }
+                Err(_) => {
+                    self.detach_field(field_id);
+                    widget_ids.push(field_id);
+                }
+            }
+        }
+
+        let mut used_states: Vec<Vec<u8>> = Vec::new();
+        let mut selected = None;
+        for (i, &widget_id) in widget_ids.iter().enumerate() {
+            let mut widget = match self.doc.objects.get(&widget_id).map(Object::as_dict) {
+                Some(Ok(widget)) => widget.clone(),
+                _ => continue,
+            };
+            for key in &[&b"FT"[..], b"T", b"TU", b"TM", b"Ff", b"V", b"DV"] {
+                widget.remove(key);
+            }
+            widget.set("Parent", Object::Reference(parent_id));
+
+            let mut ap = widget
+                .get(b"AP")
+                .and_then(|ap| ap.resolve(&self.doc).as_dict())
+                .cloned()
+                .unwrap_or_default();
+            let on_state = ap
+                .get(b"N")
+                .and_then(|normal| normal.resolve(&self.doc).as_dict())
+                .ok()
+                .and_then(|states| {
+                    states
+                        .iter()
+                        .map(|(state, _)| state.clone())
+                        .find(|state| state != b"Off")
+                })
+                .unwrap_or_else(|| b"Yes".to_vec());
+            let mut new_state = on_state.clone();
+            if used_states.contains(&new_state) {
+                new_state.extend_from_slice((i + 1).to_string().as_bytes());
+            }
+            if new_state != on_state {
+                // The appearance dictionaries may be shared with other widgets, so the renamed
+                // state is written to copies held by this widget alone
+                for key in &[&b"N"[..], b"D"] {
+                    let states = ap
+                        .get(key)
+                        .and_then(|states| states.resolve(&self.doc).as_dict())
+                        .cloned();
+                    if let Ok(mut states) = states {
+                        if let Some(appearance) = states.remove(&on_state) {
+                            states.set(new_state.clone(), appearance);
+                        }
+                        ap.set(key.to_vec(), Object::Dictionary(states));
+                    }
+                }
+                widget.set("AP", Object::Dictionary(ap));
+            }
+            let is_on = widget
+                .get(b"AS")
+                .and_then(Object::as_name)
+                .map(|state| state != b"Off")
+                .unwrap_or(false);
+            if is_on && selected.is_none() {
+                widget.set("AS", Object::Name(new_state.clone()));
+                selected = Some(new_state.clone());
+            } else {
+                widget.set("AS", Object::Name(b"Off".to_vec()));
+            }
+            used_states.push(new_state);
+            self.doc
+                .objects
+                .insert(widget_id, Object::Dictionary(widget));
+        }
+
+        let mut parent = Dictionary::new();
+        parent.set("FT", Object::Name(b"Btn".to_vec()));
+        parent.set(
+            "Ff",
+            Object::Integer((ButtonFlags::RADIO | ButtonFlags::NO_TOGGLE_TO_OFF).bits() as i64),
+        );
+        parent.set(
+            "T",
+            Object::String(encode_text(group_name), StringFormat::Literal),
+        );
+        parent.set(
+            "Kids",
+            Object::Array(widget_ids.iter().map(|&id| Object::Reference(id)).collect()),
+        );
+        parent.set(
+            "V",
+            Object::Name(selected.unwrap_or_else(|| b"Off".to_vec())),
+        );
+        self.doc
+            .objects
+            .insert(parent_id, Object::Dictionary(parent));
+        self.push_acroform_field(parent_id);
+
+        for &i in indices.iter().rev() {
+            self.form_ids.remove(i);
+        }
+        self.form_ids.push(parent_id);
+        Ok(self.form_ids.len() - 1)
+    }
+
+    /// Fills a copy of `template` for each record and hands the saved bytes to `sink` along with
+    /// the position of the record. Records map fully qualified field names to values; names
+    /// that aren't in the form are ignored.
+    ///
+    /// The template is copied once, and only the objects a record changes are reset between
+    /// records, so this is much cheaper than loading or cloning the form for every record. Objects
+    /// added for a record, such as generated appearance streams and embedded values, are removed
+    /// again, so no output carries data from the records before it.
+    ///
+    /// The batch stops at the first record that can't be filled, such as one with a value of the
+    /// wrong type for its field, and returns the `ValueError` as an error of kind
+    /// `io::ErrorKind::InvalidInput`. The records before it have already been passed to `sink`.
+    pub fn fill_batch<I, F>(template: &Form, records: I, mut sink: F) -> io::Result<()>
+    where
+        I: IntoIterator<Item = HashMap<String, FieldValue>>,
+        F: FnMut(usize, Vec<u8>) -> io::Result<()>,
+    {
+        let mut form = Form {
+            doc: template.doc.clone(),
+            form_ids: template.form_ids.clone(),
+            track_field_modifications: template.track_field_modifications,
+            clear_xfa_on_change: template.clear_xfa_on_change,
+            regenerate_id_on_save: template.regenerate_id_on_save,
+            preserve_appearance_font: template.preserve_appearance_font,
+            embed_values_on_save: template.embed_values_on_save,
+            trim_text: template.trim_text,
+            source_len: template.source_len,
+        };
+        // Besides the fields, saving and clearing the XFA form change the catalog, the
+        // interactive form dictionary and the tree of embedded files
+        let mut document_ids: Vec<ObjectId> = template
+            .doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .into_iter()
+            .collect();
+        if let Ok(catalog) = template.doc.catalog() {
+            for key in &[&b"AcroForm"[..], b"Names"] {
+                document_ids.extend(catalog.get(key).and_then(Object::as_reference));
+            }
+            document_ids.extend(
+                catalog
+                    .get(b"Names")
+                    .and_then(|names| names.resolve(&template.doc).as_dict())
+                    .and_then(|names| names.get(b"EmbeddedFiles"))
+                    .and_then(Object::as_reference),
+            );
+        }
+        for (i, record) in records.into_iter().enumerate() {
+            let mut touched = Vec::new();
+            let mut result = Ok(());
+            for (name, value) in record {
+                for n in form.indices_by_name(&name) {
+                    touched.push(form.form_ids[n]);
+                    touched.extend(form.get_widget_ids(n));
+                    result = result.and(form.set_value(n, value.clone()));
+                }
+            }
+            let mut bytes = Vec::new();
+            let saved = result
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+                .and_then(|_| form.save_to(&mut bytes));
+            // Put back the objects the record changed and drop the ones it added, so the next
+            // record starts from the template
+            form.doc
+                .objects
+                .retain(|id, _| template.doc.objects.contains_key(id));
+            for id in touched.iter().chain(&document_ids) {
+                if let Some(object) = template.doc.objects.get(id) {
+                    form.doc.objects.insert(*id, object.clone());
+                }
+            }
+            // Only the template's objects are left, so any it has beyond them were removed
+            if form.doc.objects.len() < template.doc.objects.len() {
+                for (&id, object) in &template.doc.objects {
+                    form.doc.objects.entry(id).or_insert_with(|| object.clone());
+                }
+            }
+            form.doc.max_id = template.doc.max_id;
+            form.doc.trailer = template.doc.trailer.clone();
+            saved?;
+            sink(i, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the XFA form from the document ("XFA" in the interactive form dictionary, and the
+    /// "NeedsRendering" flag of the catalog), so viewers show the AcroForm fields instead. This
+    /// makes changes to the fields of hybrid forms visible; see `form_kind`.
+    pub fn drop_xfa(&mut self) {
+        if let Some(acroform) = self.get_acroform_mut() {
+            acroform.remove(b"XFA");
+        }
+        let root_id = self
+            .doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .ok();
+        if let Some(catalog) = root_id
+            .and_then(|root_id| self.doc.objects.get_mut(&root_id))
+            .and_then(|catalog| catalog.as_dict_mut().ok())
+        {
+            catalog.remove(b"NeedsRendering");
+        }
+    }
+
+    /// Flattens the fields at the given indices: each of their widgets' current appearance is
+    /// drawn into the page content, and the widgets and fields are removed from the document, so
+    /// their values can no longer be changed. All other fields stay interactive.
+    ///
+    /// Text and combo box fields without an appearance get one generated first, which fails with
+    /// `ValueError::MissingDefaultAppearance` as in `generate_appearance`. The flattened fields
+    /// are removed from the form, so the indices of the fields after them shift down.
+    ///
+    /// # Panics
+    /// Will panic if any index is larger than the number of fields
+    pub fn flatten_fields(&mut self, indices: &[usize]) -> Result<(), ValueError> {
+        for &n in indices {
+            match self.get_type(n) {
+                FieldType::Text | FieldType::ComboBox
+                    if self.get_appearance_stream(n).is_none() =>
+                {
+                    self.generate_appearance(n)?
+                }
+                _ => (),
+            }
+        }
+        let field_ids: BTreeSet<ObjectId> = indices.iter().map(|&n| self.form_ids[n]).collect();
+        let mut drawn: HashMap<ObjectId, Vec<u8>> = HashMap::new();
+        for &n in indices {
+            for widget_id in self.get_widget_ids(n) {
+                self.flatten_widget(widget_id, &mut drawn);
+                // A field merged with its widget is removed once it is detached from its parent
+                if !field_ids.contains(&widget_id) {
+                    self.doc.objects.remove(&widget_id);
+                }
+            }
+        }
+        for (page_id, content) in drawn {
+            // Isolate the page's own graphics state from the flattened appearances
+            let before = self
+                .doc
+                .add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+            let mut after = b"Q\n".to_vec();
+            after.extend_from_slice(&content);
+            let after = self.doc.add_object(Stream::new(Dictionary::new(), after));
+            let page = match self
+                .doc
+                .objects
+                .get_mut(&page_id)
+                .and_then(|page| page.as_dict_mut().ok())
+            {
+                Some(page) => page,
+                None => continue,
+            };
+            let mut contents = match page.get(b"Contents") {
+                Ok(Object::Array(contents)) => contents.clone(),
+                Ok(contents) => vec![contents.clone()],
+                Err(_) => Vec::new(),
+            };
+            contents.insert(0, Object::Reference(before));
+            contents.push(Object::Reference(after));
+            page.set("Contents", Object::Array(contents));
+        }
+        for &field_id in &field_ids {
+            self.detach_field(field_id);
+            self.doc.objects.remove(&field_id);
+        }
+        self.form_ids.retain(|id| !field_ids.contains(id));
+        Ok(())
+    }
+
+    /// Removes all JavaScript from the document: the document-level scripts in the "JavaScript"
+    /// name tree, and every JavaScript action, whether it is used in an additional-actions ("AA")
+    /// dictionary, which is where fields keep their format, validation and calculation scripts,
+    /// as an "OpenAction" or a click action, or chained from another action with "Next". Other
+    /// actions are kept, and the actions a script chained to take its place.
+    pub fn strip_javascript(&mut self) {
+        let mut removed = BTreeMap::new();
+        for (&id, object) in &self.doc.objects {
+            if let Ok(action) = object.as_dict() {
+                if is_javascript_action(action) {
+                    removed.insert(id, next_actions(action));
+                }
+            }
+        }
+        for id in removed.keys() {
+            self.doc.objects.remove(id);
+        }
+
+        if let Ok(root_id) = self.doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            let names_id = self
+                .doc
+                .objects
+                .get(&root_id)
+                .and_then(|catalog| catalog.as_dict().ok())
+                .and_then(|catalog| catalog.get(b"Names").ok())
+                .and_then(|names| names.as_reference().ok());
+            let names = match names_id {
+                Some(names_id) => self.doc.objects.get_mut(&names_id),
+                None => self
+                    .doc
+                    .objects
+                    .get_mut(&root_id)
+                    .and_then(|catalog| catalog.as_dict_mut().ok())
+                    .and_then(|catalog| catalog.get_mut(b"Names").ok()),
+            };
+            if let Some(&mut Object::Dictionary(ref mut names)) = names {
+                names.remove(b"JavaScript");
+            }
+        }
+
+        // Additional-actions dictionaries kept in objects of their own
+        let additional_actions_ids: BTreeSet<ObjectId> = self
+            .doc
+            .objects
+            .values()
+            .filter_map(|object| match *object {
+                Object::Dictionary(ref dict) => Some(dict),
+                Object::Stream(ref stream) => Some(&stream.dict),
+                _ => None,
+            })
+            .filter_map(|dict| dict.get(b"AA").and_then(Object::as_reference).ok())
+            .collect();
+        let ids: Vec<ObjectId> = self.doc.objects.keys().cloned().collect();
+        for id in ids {
+            // Taken out of the document so the rest of it can be read while it is changed
+            let mut object = match self.doc.objects.remove(&id) {
+                Some(object) => object,
+                None => continue,
+            };
+            match object {
+                Object::Dictionary(ref mut dict) => strip_script_actions(
+                    dict,
+                    additional_actions_ids.contains(&id),
+                    &removed,
+                    &self.doc,
+                ),
+                Object::Stream(ref mut stream) => {
+                    strip_script_actions(&mut stream.dict, false, &removed, &self.doc)
+                }
+                _ => (),
+            }
+            self.doc.objects.insert(id, object);
+        }
+    }
+
+    /// Generates the normal appearance of a text field or combo box from its value, so that it
+    /// displays correctly in viewers that don't generate appearances themselves. The text is
+    /// drawn with the font, size and color of the field's default appearance ("DA") and
+    /// positioned according to its justification ("Q"). Multiline text fields have their text
+    /// wrapped to the width of the field.
+    ///
+    /// # Panics
+    /// Will panic if n is larger than the number of fields
+    pub fn generate_appearance(&mut self, n: usize) -> Result<(), ValueError> {
+        match self.get_type(n) {
+            FieldType::Text | FieldType::ComboBox => (),
+            _ => return Err(ValueError::TypeMismatch),
+        }
+        let da = match self
+            .get_default_appearance(n)
+            .and_then(|da| DefaultAppearance::parse(&da))
+        {
+            Some(da) => da,
+            None => return Err(ValueError::MissingDefaultAppearance),
+        };
+        let font = self.get_form_font(&da.font);
+        self.write_text_appearance(n, &da, font);
+        Ok(())
+    }
+
+    /// Re-encodes the string values ("V") and options ("Opt") of every field consistently:
+    /// values with characters outside Latin-1 as UTF-16BE with a byte order mark, and all
+    /// others as Latin-1 literals. Values that were mistakenly written as UTF-8, which viewers
+    /// display garbled, are decoded as UTF-8 first.
+    pub fn normalize_encoding(&mut self) {
+        for id in self.form_ids.clone() {
+            let field = match self.doc.objects.get_mut(&id) {
+                Some(&mut Object::Dictionary(ref mut field)) => field,
+                _ => continue,
+            };
+            for key in &[&b"V"[..], b"Opt"] {
+                if let Ok(value) = field.get_mut(key) {
+                    normalize_strings(value);
+                }
+            }
+        }
+    }
+
+    /// Removes every annotation that isn't a form widget, such as text notes, stamps and links,
+    /// from all pages, leaving the form's widgets in place
+    pub fn strip_annotations(&mut self) {
+        let pages: Vec<ObjectId> = self.doc.get_pages().values().cloned().collect();
+        for (page, page_id) in pages.into_iter().enumerate() {
+            let strip = self.non_widget_annotations(page);
+            if let Some(annots) = self.get_annots_mut(page_id) {
+                annots.retain(|annot| match *annot {
+                    Object::Reference(ref annot_id) => !strip.contains(annot_id),
+                    Object::Dictionary(ref annot) => {
+                        annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Widget")
+                    }
+                    _ => true,
+                });
+            }
+        }
+    }
+
+    /// Sets the document's viewer preferences. Other entries of the catalog's
+    /// "ViewerPreferences" are kept.
+    pub fn set_viewer_preferences(&mut self, preferences: ViewerPreferences) {
+        let root_id = match self.doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            Ok(root_id) => root_id,
+            Err(_) => return,
+        };
+        let existing = self
+            .doc
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"ViewerPreferences").ok());
+        let preferences_id = existing.and_then(|existing| existing.as_reference().ok());
+        let mut dict = existing
+            .and_then(|existing| existing.resolve(&self.doc).as_dict().ok())
+            .cloned()
+            .unwrap_or_default();
+        dict.set("HideToolbar", Object::Boolean(preferences.hide_toolbar));
+        dict.set("HideMenubar", Object::Boolean(preferences.hide_menubar));
+        dict.set(
+            "DisplayDocTitle",
+            Object::Boolean(preferences.display_doc_title),
+        );
+        match preferences_id {
+            Some(preferences_id) => {
+                self.doc
+                    .objects
+                    .insert(preferences_id, Object::Dictionary(dict));
+            }
+            None => {
+                if let Some(&mut Object::Dictionary(ref mut catalog)) =
+                    self.doc.objects.get_mut(&root_id)
+                {
+                    catalog.set("ViewerPreferences", Object::Dictionary(dict));
+                }
+            }
+        }
+    }
+
+    /// Prepares the form for saving by asking viewers to generate appearances (setting the
+    /// form's "NeedAppearances" flag) only if some field needs one, as listed by
+    /// `stale_appearances`. Otherwise the flag is left as it is, so viewers don't needlessly
+    /// regenerate the appearances of every field.
+    pub fn finalize(&mut self) {
+        if self.stale_appearances().is_empty() {
+            return;
+        }
+        if let Some(acroform) = self.get_acroform_mut() {
+            acroform.set("NeedAppearances", Object::Boolean(true));
+        }
+    }
+
+    /// Generates the appearance of every text field and combo box that has a value, like
+    /// `generate_appearance`, then clears the form's "NeedAppearances" flag so that viewers use
+    /// these appearances instead of regenerating them. Returns the first error
+    /// `generate_appearance` returns, in which case the flag is left as it is.
+    ///
+    /// The flag is also left as it is if other fields are still missing appearances (see
+    /// `stale_appearances`), such as list boxes, which this doesn't draw, so that viewers still
+    /// generate theirs.
+    pub fn render_all_appearances(&mut self) -> Result<(), ValueError> {
+        for n in 0..self.len() {
+            match self.get_type(n) {
+                FieldType::Text | FieldType::ComboBox => (),
+                _ => continue,
+            }
+            if self.get_inheritable(n, b"V").is_some() {
+                self.generate_appearance(n)?;
+            }
+        }
+        if !self.stale_appearances().is_empty() {
+            return Ok(());
+        }
+        if let Some(acroform) = self.get_acroform_mut() {
+            acroform.set("NeedAppearances", Object::Boolean(false));
+        }
+        Ok(())
+    }
+
+    /// Splits the form into one form per page. Each contains only its page, the fields with
+    /// widgets on that page, and its own copy of everything the page uses, and keeps the
+    /// options set on this form. A page whose copy can't be loaded as a form gives `None`, so
+    /// the forms stay at the indices of their pages.
+    pub fn split_by_page(&self) -> Vec<Option<Form>> {
+        let page_count = self.doc.get_pages().len();
+        (0..page_count)
+            .map(|page| self.extract_page(page))
+            .collect()
+    }
+
+    /// Saves the form to the specified path, packing objects into compressed object streams
+    /// with a cross-reference stream (PDF 1.5). Streams are compressed as well. The output is
+    /// usually far smaller than what `save` writes, and comparable to forms that were loaded from
+    /// compressed files.
+    pub fn save_compressed<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.save_compressed_to(&mut file)?;
+        file.flush()
+    }
+
+    /// Saves the form to the target, packing objects into compressed object streams with a
+    /// cross-reference stream. See `save_compressed`.
+    ///
+    /// Like `save_to`, this writes as it goes; only the object stream being packed is held in
+    /// memory.
+    pub fn save_compressed_to<W: Write>(&mut self, target: &mut W) -> Result<(), io::Error> {
+        self.prepare_save();
+        self.doc.compress();
+        writer::write_compressed(&self.doc, target)
+    }
+
+    /// Makes the changes to the document that are due on every save: embedding the values of
+    /// the fields and giving the document a new identifier, if enabled
+    fn prepare_save(&mut self) {
+        if self.embed_values_on_save {
+            self.embed_values();
+        }
+        if !self.regenerate_id_on_save {
+            return;
+        }
+        let permanent = match self.document_id() {
+            Some((permanent, _)) => permanent,
+            None => random_id(),
+        };
+        self.doc.trailer.set(
+            "ID",
+            Object::Array(vec![
+                Object::String(permanent, StringFormat::Hexadecimal),
+                Object::String(random_id(), StringFormat::Hexadecimal),
+            ]),
+        );
+    }
+
+    /// Attaches the values of the fields to the document as "form-data.json", replacing any
+    /// earlier attachment of them
+    fn embed_values(&mut self) {
+        let mut json = String::from("{");
+        for (i, name) in self.csv_headers().into_iter().enumerate() {
+            let value = match self.indices_by_export_name(&name).first() {
+                Some(&n) => self.get_value_text(n),
+                None => String::new(),
+            };
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(&format!("{}:{}", json_string(&name), json_string(&value)));
+        }
+        json.push('}');
+
+        let mut params = Dictionary::new();
+        params.set("Size", Object::Integer(json.len() as i64));
+        let mut file = Dictionary::new();
+        file.set("Type", Object::Name(b"EmbeddedFile".to_vec()));
+        file.set("Subtype", Object::Name(b"application/json".to_vec()));
+        file.set("Params", Object::Dictionary(params));
+        let file_id = self.doc.add_object(Stream::new(file, json.into_bytes()));
+        let mut embedded_file = Dictionary::new();
+        embedded_file.set("F", Object::Reference(file_id));
+        let mut file_spec = Dictionary::new();
+        file_spec.set("Type", Object::Name(b"Filespec".to_vec()));
+        file_spec.set(
+            "F",
+            Object::String(VALUES_ATTACHMENT.as_bytes().to_vec(), StringFormat::Literal),
+        );
+        file_spec.set(
+            "UF",
+            Object::String(VALUES_ATTACHMENT.as_bytes().to_vec(), StringFormat::Literal),
+        );
+        file_spec.set("AFRelationship", Object::Name(b"Data".to_vec()));
+        file_spec.set("EF", Object::Dictionary(embedded_file));
+
+        let root_id = match self.doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            Ok(root_id) => root_id,
+            Err(_) => return,
+        };
+        let catalog = self.doc.catalog().cloned().unwrap_or_default();
+        let names_id = catalog.get(b"Names").and_then(Object::as_reference).ok();
+        let mut names = catalog
+            .get(b"Names")
+            .and_then(|names| names.resolve(&self.doc).as_dict())
+            .cloned()
+            .unwrap_or_default();
+        let tree_id = names
+            .get(b"EmbeddedFiles")
+            .and_then(Object::as_reference)
+            .ok();
+        let mut tree = names
+            .get(b"EmbeddedFiles")
+            .and_then(|tree| tree.resolve(&self.doc).as_dict())
+            .cloned()
+            .unwrap_or_default();
+
+        let key = Object::String(VALUES_ATTACHMENT.as_bytes().to_vec(), StringFormat::Literal);
+        let file_spec_id = self.doc.add_object(file_spec);
+        let mut replaced = None;
+        let is_leaf = tree.get(b"Names").and_then(Object::as_array).is_ok();
+        if is_leaf {
+            if let Ok(entries) = tree.get_mut(b"Names").and_then(Object::as_array_mut) {
+                // An earlier attachment is replaced, and the keys are kept sorted
+                let name = VALUES_ATTACHMENT.as_bytes();
+                if let Some(i) =
+                    (0..entries.len() / 2).find(|&i| entries[2 * i].as_str().ok() == Some(name))
+                {
+                    replaced = entries.remove(2 * i + 1).as_reference().ok();
+                    entries.remove(2 * i);
+                }
+                let position = (0..entries.len() / 2)
+                    .find(|&i| {
+                        entries[2 * i]
+                            .as_str()
+                            .map(|other| other > name)
+                            .unwrap_or(false)
+                    })
+                    .unwrap_or(entries.len() / 2);
+                entries.insert(2 * position, Object::Reference(file_spec_id));
+                entries.insert(2 * position, key);
+            }
+        } else if tree.has(b"Kids") {
+            // Add a leaf of its own rather than searching the tree for the right one
+            let mut leaf = Dictionary::new();
+            leaf.set("Limits", Object::Array(vec![key.clone(), key.clone()]));
+            leaf.set(
+                "Names",
+                Object::Array(vec![key, Object::Reference(file_spec_id)]),
+            );
+            let leaf_id = self.doc.add_object(leaf);
+            if let Ok(kids) = tree.get_mut(b"Kids").and_then(Object::as_array_mut) {
+                kids.push(Object::Reference(leaf_id));
+            }
+        } else {
+            tree.set(
+                "Names",
+                Object::Array(vec![key, Object::Reference(file_spec_id)]),
+            );
+        }
+
+        // Drop the earlier attachment's file spec and file
+        if let Some(replaced) = replaced.and_then(|id| self.doc.objects.remove(&id)) {
+            let file_id = replaced
+                .as_dict()
+                .and_then(|file_spec| file_spec.get(b"EF"))
+                .and_then(Object::as_dict)
+                .and_then(|embedded_file| embedded_file.get(b"F"))
+                .and_then(Object::as_reference);
+            if let Ok(file_id) = file_id {
+                self.doc.objects.remove(&file_id);
+            }
+        }
+
+        match tree_id {
+            Some(tree_id) => {
+                self.doc.objects.insert(tree_id, Object::Dictionary(tree));
+            }
+            None => names.set("EmbeddedFiles", Object::Dictionary(tree)),
+        }
+        match names_id {
+            Some(names_id) => {
+                self.doc.objects.insert(names_id, Object::Dictionary(names));
+            }
+            None => {
+                if let Some(&mut Object::Dictionary(ref mut catalog)) =
+                    self.doc.objects.get_mut(&root_id)
+                {
+                    catalog.set("Names", Object::Dictionary(names));
+                }
+            }
+        }
+    }
+
+    /// Gets the JavaScript the field runs on `trigger`, one of the keys of its additional-actions
+    /// ("AA") dictionary, such as "F" for its format script or "K" for its keystroke script
+    fn get_script(&self, n: usize, trigger: &[u8]) -> Option<String> {
+        let field = self.doc.objects.get(&self.form_ids[n])?.as_dict().ok()?;
+        [field, self.get_widget(n)]
+            .iter()
+            .filter_map(|dict| {
+                let actions = dict.get(b"AA").ok()?.resolve(&self.doc).as_dict().ok()?;
+                actions.get(trigger).ok()
+            })
+            .find_map(
+                |action| match self.parse_action(action.resolve(&self.doc)) {
+                    Some(ButtonAction::JavaScript(script)) => Some(script),
+                    _ => None,
+                },
+            )
+    }
+
+    /// Parses an action dictionary
+    fn parse_action(&self, action: &Object) -> Option<ButtonAction> {
+        let action = action.as_dict().ok()?;
+        let text = |key: &[u8]| -> String {
+            match action.get(key).map(|value| value.resolve(&self.doc)) {
+                Ok(Object::String(text, _)) => decode_text(text),
+                Ok(Object::Name(name)) => String::from_utf8_lossy(name).into_owned(),
+                Ok(Object::Stream(stream)) => {
+                    let mut stream = stream.clone();
+                    stream.decompress();
+                    decode_text(&stream.content)
+                }
+                Ok(Object::Dictionary(file_spec)) => file_spec
+                    .get(b"F")
+                    .and_then(Object::as_str)
+                    .map(decode_text)
+                    .unwrap_or_default(),
+                _ => String::new(),
+            }
+        };
+        let kind = action.get(b"S").and_then(Object::as_name).ok()?;
+        Some(match kind {
+            b"JavaScript" => ButtonAction::JavaScript(text(b"JS")),
+            b"URI" => ButtonAction::Uri(text(b"URI")),
+            b"Named" => ButtonAction::Named(text(b"N")),
+            b"SubmitForm" => ButtonAction::SubmitForm(text(b"F")),
+            b"ResetForm" => ButtonAction::ResetForm,
+            b"GoTo" => ButtonAction::GoTo,
+            _ => ButtonAction::Other(String::from_utf8_lossy(kind).into_owned()),
+        })
+    }
+
+    /// Gets the default appearance string ("DA") of the field, falling back to the form's default
+    fn get_default_appearance(&self, n: usize) -> Option<String> {
+        let da = match self.get_inheritable(n, b"DA") {
+            Some(da) => da,
+            None => self.get_acroform()?.get(b"DA").ok()?.resolve(&self.doc),
+        };
+        da.as_str().ok().map(decode_text)
+    }
+
+    /// Removes the widget from its page and, unless it is hidden, adds its normal appearance to
+    /// the page's resources as a form XObject. The operators drawing it over the widget's
+    /// rectangle are appended to the page's entry in `drawn`.
+    fn flatten_widget(&mut self, widget_id: ObjectId, drawn: &mut HashMap<ObjectId, Vec<u8>>) {
+        let page_id = match self.get_widget_page(widget_id) {
+            Some(page_id) => page_id,
+            None => return,
+        };
+        let widget = match self
+            .doc
+            .objects
+            .get(&widget_id)
+            .and_then(|w| w.as_dict().ok())
+        {
+            Some(widget) => widget.clone(),
+            None => return,
+        };
+        self.remove_annotation(page_id, widget_id);
+        // Hidden widgets leave nothing behind
+        if widget.get(b"F").and_then(Object::as_i64).unwrap_or(0) & 2 != 0 {
+            return;
+        }
+        let rect: Vec<f64> = match widget.get(b"Rect").map(|rect| rect.resolve(&self.doc)) {
+            Ok(Object::Array(rect)) => rect.iter().filter_map(as_number).collect(),
+            _ => return,
+        };
+        if rect.len() != 4 {
+            return;
+        }
+        let normal = match widget
+            .get_deref(b"AP", &self.doc)
+            .and_then(Object::as_dict)
+            .and_then(|ap| ap.get(b"N"))
+        {
+            Ok(normal) => normal.clone(),
+            Err(_) => return,
+        };
+        let appearance = match *normal.resolve(&self.doc) {
+            Object::Dictionary(ref states) => match widget.get(b"AS").and_then(Object::as_name) {
+                Ok(state) => match states.get(state) {
+                    Ok(appearance) => appearance.clone(),
+                    Err(_) => return,
+                },
+                Err(_) => return,
+            },
+            _ => normal,
+        };
+        let xobject_id = match appearance {
+            Object::Reference(id) => id,
+            Object::Stream(stream) => self.doc.add_object(stream),
+            _ => return,
+        };
+        let stream = match self
+            .doc
+            .objects
+            .get_mut(&xobject_id)
+            .and_then(|stream| stream.as_stream_mut().ok())
+        {
+            Some(stream) => stream,
+            None => return,
+        };
+        stream.dict.set("Type", Object::Name(b"XObject".to_vec()));
+        stream.dict.set("Subtype", Object::Name(b"Form".to_vec()));
+        let numbers = |key: &[u8]| -> Vec<f64> {
+            stream
+                .dict
+                .get(key)
+                .and_then(Object::as_array)
+                .map(|values| values.iter().filter_map(as_number).collect())
+                .unwrap_or_default()
+        };
+        let bbox = numbers(b"BBox");
+        let mut matrix = numbers(b"Matrix");
+        if bbox.len() != 4 {
+            return;
+        }
+        if matrix.len() != 6 {
+            matrix = vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        }
+        // Map the bounding box, as transformed by the form's matrix, onto the widget's rectangle
+        let corners = [
+            (bbox[0], bbox[1]),
+            (bbox[0], bbox[3]),
+            (bbox[2], bbox[1]),
+            (bbox[2], bbox[3]),
+        ];
+        let xs: Vec<f64> = corners
+            .iter()
+            .map(|&(x, y)| matrix[0] * x + matrix[2] * y + matrix[4])
+            .collect();
+        let ys: Vec<f64> = corners
+            .iter()
+            .map(|&(x, y)| matrix[1] * x + matrix[3] * y + matrix[5])
+            .collect();
+        let min = |values: &[f64]| values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = |values: &[f64]| values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let (box_x, box_y) = (min(&xs), min(&ys));
+        let (rect_x, rect_y) = (rect[0].min(rect[2]), rect[1].min(rect[3]));
+        let scale = |rect_size: f64, box_size: f64| {
+            if box_size == 0.0 {
+                1.0
+            } else {
+                rect_size / box_size
+            }
+        };
+        let scale_x = scale((rect[2] - rect[0]).abs(), max(&xs) - box_x);
+        let scale_y = scale((rect[3] - rect[1]).abs(), max(&ys) - box_y);
+
+        let resources = self
+            .get_inherited_page_attribute(page_id, b"Resources")
+            .and_then(|resources| resources.resolve(&self.doc).as_dict().ok().cloned());
+        let mut resources = resources.unwrap_or_default();
+        let mut xobjects = resources
+            .get(b"XObject")
+            .ok()
+            .and_then(|xobjects| xobjects.resolve(&self.doc).as_dict().ok().cloned())
+            .unwrap_or_default();
+        let mut index = 0;
+        let name = loop {
+            let name = format!("FlatField{}", index);
+            if !xobjects.has(name.as_bytes()) {
+                break name;
+            }
+            index += 1;
+        };
+        xobjects.set(name.clone(), Object::Reference(xobject_id));
+        resources.set("XObject", Object::Dictionary(xobjects));
+        if let Some(page) = self
+            .doc
+            .objects
+            .get_mut(&page_id)
+            .and_then(|page| page.as_dict_mut().ok())
+        {
+            page.set("Resources", Object::Dictionary(resources));
+        }
+        let content = drawn.entry(page_id).or_default();
+        let _ = writeln!(
+            content,
+            "q {} 0 0 {} {} {} cm /{} Do Q",
+            scale_x,
+            scale_y,
+            rect_x - box_x * scale_x,
+            rect_y - box_y * scale_y,
+            name
+        );
+    }
+
+    /// Removes the annotation from the annotations array ("Annots") of the page
+    fn remove_annotation(&mut self, page_id: ObjectId, annot_id: ObjectId) {
+        if let Some(annots) = self.get_annots_mut(page_id) {
+            annots.retain(|annot| annot.as_reference().ok() != Some(annot_id));
+        }
+    }
+
+    /// Gets the annotations array ("Annots") of a page for modification
+    fn get_annots_mut(&mut self, page_id: ObjectId) -> Option<&mut Vec<Object>> {
+        let annots = self
+            .doc
+            .objects
+            .get(&page_id)
+            .and_then(|page| page.as_dict().ok())
+            .and_then(|page| page.get(b"Annots").ok())
+            .and_then(|annots| annots.as_reference().ok());
+        // "Annots" may be kept in an object of its own
+        let annots = match annots {
+            Some(annots_id) => self.doc.objects.get_mut(&annots_id),
+            None => self
+                .doc
+                .objects
+                .get_mut(&page_id)
+                .and_then(|page| page.as_dict_mut().ok())
+                .and_then(|page| page.get_mut(b"Annots").ok()),
+        };
+        match annots {
+            Some(&mut Object::Array(ref mut annots)) => Some(annots),
+            _ => None,
         }
     }
 
-    pub fn set_check_box(&mut self, n: usize, is_checked: bool) -> Result<(),ValueError> {
+    /// Gets the page the widget is on, from its "P" entry or else by searching the pages'
+    /// annotations
+    fn get_widget_page(&self, widget_id: ObjectId) -> Option<ObjectId> {
+        let pages = self.doc.get_pages();
+        let page_id = self
+            .doc
+            .objects
+            .get(&widget_id)
+            .and_then(|widget| widget.as_dict().ok())
+            .and_then(|widget| widget.get(b"P").ok())
+            .and_then(|page| page.as_reference().ok());
+        if let Some(page_id) = page_id {
+            if pages.values().any(|&id| id == page_id) {
+                return Some(page_id);
+            }
+        }
+        // "P" is optional, so fall back to looking for the widget in each page's annotations
+        pages.values().cloned().find(|page_id| {
+            self.doc
+                .objects
+                .get(page_id)
+                .and_then(|page| page.as_dict().ok())
+                .and_then(|page| page.get(b"Annots").ok())
+                .and_then(|annots| annots.resolve(&self.doc).as_array().ok())
+                .into_iter()
+                .flatten()
+                .any(|annot| annot.as_reference().ok() == Some(widget_id))
+        })
+    }
+
+    /// Checks whether the field's value ("V"), if it has one, is stored as the type of object
+    /// its field type calls for. See `validate_value_types`.
+    fn has_valid_value_type(&self, n: usize) -> bool {
+        let value = match self.get_inheritable(n, b"V") {
+            Some(value) => value,
+            None => return true,
+        };
         match self.get_type(n) {
-            FieldType::CheckBox => {
-                let state = Object::Name({if is_checked {"On"} else {"Off"}}.to_owned().into_bytes());
-                let field = self.doc.objects.get_mut(&self.form_ids[n]).unwrap().as_dict_mut().unwrap();
-                field.set("V",state.clone());
-                field.set("AS",state);
-                Ok(())
+            FieldType::Text => matches!(*value, Object::String(..) | Object::Stream(_)),
+            FieldType::ListBox | FieldType::ComboBox => match *value {
+                Object::String(..) => true,
+                Object::Array(ref values) => values
+                    .iter()
+                    .all(|value| matches!(*value.resolve(&self.doc), Object::String(..))),
+                _ => false,
             },
-            _ => Err(ValueError::TypeMismatch)
+            FieldType::CheckBox | FieldType::Radio => matches!(*value, Object::Name(_)),
+            FieldType::Button => true,
+        }
+    }
+
+    /// Writes a normal appearance showing the field's text with the given default appearance to
+    /// each of its widgets. `font` is the font resource named in `da`, if it could be found.
+    fn write_text_appearance(&mut self, n: usize, da: &DefaultAppearance, font: Option<Object>) {
+        let text = match self.get_inheritable(n, b"V") {
+            Some(Object::String(text, _)) => decode_text(text),
+            _ => String::new(),
+        };
+        let quadding = self.get_quadding(n);
+        let multiline = self.get_text_flags(n).contains(TextFlags::MULTILINE);
+        let base_font = font
+            .as_ref()
+            .and_then(|font| font.resolve(&self.doc).as_dict().ok())
+            .and_then(|font| font.get(b"BaseFont").ok())
+            .and_then(|name| name.as_name_str().ok())
+            .unwrap_or("Helvetica")
+            .to_owned();
+
+        for widget_id in self.get_widget_ids(n) {
+            let (width, height) = match self.get_widget_size(widget_id) {
+                Some(size) => size,
+                None => continue,
+            };
+            let content =
+                appearance::text_content(da, &base_font, &text, width, height, quadding, multiline);
+            let mut resources = Dictionary::new();
+            if let Some(ref font) = font {
+                let mut fonts = Dictionary::new();
+                fonts.set(da.font.clone(), font.clone());
+                resources.set("Font", Object::Dictionary(fonts));
+            }
+            let mut stream_dict = Dictionary::new();
+            stream_dict.set("Type", Object::Name(b"XObject".to_vec()));
+            stream_dict.set("Subtype", Object::Name(b"Form".to_vec()));
+            stream_dict.set(
+                "BBox",
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::Integer(0),
+                    Object::Real(width),
+                    Object::Real(height),
+                ]),
+            );
+            stream_dict.set("Resources", Object::Dictionary(resources));
+            let stream_id = self.doc.add_object(Stream::new(stream_dict, content));
+            let mut ap = Dictionary::new();
+            ap.set("N", Object::Reference(stream_id));
+            if let Some(widget) = self
+                .doc
+                .objects
+                .get_mut(&widget_id)
+                .and_then(|widget| widget.as_dict_mut().ok())
+            {
+                widget.set("AP", Object::Dictionary(ap));
+            }
+        }
+    }
+
+    /// Reads the font, font size and color from the text of the field's existing normal
+    /// appearance, along with the font resource it uses
+    fn get_appearance_text_settings(
+        &self,
+        n: usize,
+    ) -> Option<(DefaultAppearance, Option<Object>)> {
+        let appearance = self
+            .get_widget(n)
+            .get_deref(b"AP", &self.doc)
+            .and_then(Object::as_dict)
+            .and_then(|ap| ap.get_deref(b"N", &self.doc))
+            .and_then(Object::as_stream)
+            .ok()?;
+        let mut stream = appearance.clone();
+        stream.decompress();
+        let content = String::from_utf8_lossy(&stream.content).into_owned();
+        // Only the text object holds the text settings; colors before it are for the border
+        let text_object = content
+            .find("BT")
+            .map(|start| &content[start..])
+            .unwrap_or(&content);
+        let da = DefaultAppearance::parse(text_object)?;
+        let font = appearance
+            .dict
+            .get_deref(b"Resources", &self.doc)
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get_deref(b"Font", &self.doc))
+            .and_then(Object::as_dict)
+            .and_then(|fonts| fonts.get(da.font.as_bytes()))
+            .ok()
+            .cloned()
+            .or_else(|| self.get_form_font(&da.font));
+        Some((da, font))
+    }
+
+    /// Gets the font resource with the given name from the form's default resources ("DR")
+    fn get_form_font(&self, name: &str) -> Option<Object> {
+        self.get_acroform()
+            .and_then(|acroform| acroform.get(b"DR").ok())
+            .and_then(|dr| dr.resolve(&self.doc).as_dict().ok())
+            .and_then(|dr| dr.get(b"Font").ok())
+            .and_then(|fonts| fonts.resolve(&self.doc).as_dict().ok())
+            .and_then(|fonts| fonts.get(name.as_bytes()).ok())
+            .cloned()
+    }
+
+    /// Adds the ids of the objects that references in `object` point to but that don't exist
+    fn find_missing_references(&self, object: &Object, missing: &mut BTreeSet<ObjectId>) {
+        match *object {
+            Object::Reference(id) if !self.doc.objects.contains_key(&id) => {
+                missing.insert(id);
+            }
+            Object::Array(ref items) => {
+                for item in items {
+                    self.find_missing_references(item, missing);
+                }
+            }
+            Object::Dictionary(ref dict) => {
+                for (_, value) in dict.iter() {
+                    self.find_missing_references(value, missing);
+                }
+            }
+            Object::Stream(ref stream) => {
+                for (_, value) in stream.dict.iter() {
+                    self.find_missing_references(value, missing);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Gets the width and height of a widget's "Rect"
+    fn get_widget_size(&self, widget_id: ObjectId) -> Option<(f64, f64)> {
+        let rect = self
+            .doc
+            .objects
+            .get(&widget_id)?
+            .as_dict()
+            .ok()?
+            .get(b"Rect")
+            .ok()?
+            .resolve(&self.doc)
+            .as_array()
+            .ok()?;
+        let coords: Vec<f64> = rect.iter().filter_map(as_number).collect();
+        if coords.len() != 4 {
+            return None;
+        }
+        Some(((coords[2] - coords[0]).abs(), (coords[3] - coords[1]).abs()))
+    }
+
+    /// Gets the indices of the fields whose fully qualified name is `name`
+    fn indices_by_name(&self, name: &str) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| self.get_full_name(i).as_deref() == Some(name))
+            .collect()
+    }
+
+    /// Gets the name the field of the given index is exported under: its mapping name, or its
+    /// full name if it has none
+    fn get_export_name(&self, n: usize) -> Option<String> {
+        self.get_mapping_name(n).or_else(|| self.get_full_name(n))
+    }
+
+    /// Gets the indices of the fields whose export name is `name`
+    fn indices_by_export_name(&self, name: &str) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| self.get_export_name(i).as_deref() == Some(name))
+            .collect()
+    }
+
+    /// Gets the on states of the buttons of a radio button group
+    fn get_radio_options(&self, n: usize) -> Vec<String> {
+        let mut options = Vec::new();
+        for widget_id in self.get_widget_ids(n) {
+            for state in self.get_widget_states(widget_id) {
+                if !options.contains(&state) {
+                    options.push(state);
+                }
+            }
+        }
+        options
+    }
+
+    /// Gets the names of the normal appearance states of a button widget other than "Off"
+    fn get_widget_states(&self, widget_id: ObjectId) -> Vec<String> {
+        self.get_normal_appearances(widget_id)
+            .map(|states| {
+                states
+                    .iter()
+                    .filter(|&(state, _)| state != b"Off")
+                    .map(|(state, _)| String::from_utf8_lossy(state).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets the normal appearances ("N" in "AP") of a widget, if they are a dictionary of
+    /// appearance states
+    fn get_normal_appearances(&self, widget_id: ObjectId) -> Option<&Dictionary> {
+        self.doc
+            .objects
+            .get(&widget_id)?
+            .as_dict()
+            .ok()?
+            .get(b"AP")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()?
+            .get(b"N")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()
+    }
+
+    /// Formats the value of the field as text, as used in CSV rows
+    fn get_value_text(&self, n: usize) -> String {
+        match self.get_state(n) {
+            FieldState::Button => String::new(),
+            FieldState::Radio { selected, .. } => selected,
+            FieldState::CheckBox { .. } => self
+                .checkbox_export_value(n)
+                .unwrap_or_else(|| "Off".to_owned()),
+            FieldState::ListBox { selected, .. } | FieldState::ComboBox { selected, .. } => {
+                selected.join(";")
+            }
+            FieldState::Text { text } => text,
+        }
+    }
+
+    /// Finds the first of the field's widgets whose appearance state ("AS") is an on state,
+    /// giving its position among the widgets and the state
+    fn selected_widget_state(&self, n: usize) -> Option<(usize, String)> {
+        self.get_widget_ids(n)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, widget_id)| {
+                let widget = self.doc.objects.get(&widget_id)?.as_dict().ok()?;
+                let state = widget.get(b"AS").and_then(Object::as_name).ok()?;
+                if state == b"Off" {
+                    None
+                } else {
+                    Some((i, String::from_utf8_lossy(state).into_owned()))
+                }
+            })
+            .next()
+    }
+
+    /// Gets the alternate text of the structure element the field's widget is tagged with
+    fn get_structure_alt(&self, n: usize) -> Option<String> {
+        let key = self
+            .get_widget_ids(n)
+            .into_iter()
+            .filter_map(|id| self.doc.objects.get(&id)?.as_dict().ok())
+            .find_map(|widget| widget.get(b"StructParent").and_then(Object::as_i64).ok())?;
+        let parent_tree = self
+            .doc
+            .catalog()
+            .ok()?
+            .get(b"StructTreeRoot")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()?
+            .get(b"ParentTree")
+            .ok()?
+            .resolve(&self.doc);
+        let mut entries = Vec::new();
+        self.collect_number_tree(parent_tree, &mut entries);
+        entries
+            .into_iter()
+            .find(|&(entry_key, _)| entry_key == key)?
+            .1
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()?
+            .get(b"Alt")
+            .ok()?
+            .resolve(&self.doc)
+            .as_str()
+            .ok()
+            .map(decode_text)
+    }
+
+    /// Interprets text as a value for the field, for `apply_csv_row` and `apply_lines`. Buttons
+    /// have no value.
+    fn parse_value_text(&self, n: usize, value: &str) -> Option<FieldValue> {
+        Some(match self.get_type(n) {
+            FieldType::Button => return None,
+            FieldType::Text => FieldValue::Text(value.to_owned()),
+            FieldType::CheckBox => {
+                let unchecked = ["", "off", "false", "no", "0"];
+                FieldValue::CheckBox(!unchecked.contains(&value.to_lowercase().as_str()))
+            }
+            FieldType::Radio => FieldValue::Radio(value.to_owned()),
+            FieldType::ListBox => FieldValue::ListBox(if value.is_empty() {
+                Vec::new()
+            } else {
+                value.split(';').map(str::to_owned).collect()
+            }),
+            FieldType::ComboBox => FieldValue::ComboBox(value.to_owned()),
+        })
+    }
+
+    /// Gets the field a radio button belongs to: the button itself if it is named, or else its
+    /// parent
+    fn get_radio_group_id(&self, n: usize) -> ObjectId {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        if field.has(b"T") {
+            return self.form_ids[n];
+        }
+        field
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .unwrap_or(self.form_ids[n])
+    }
+
+    /// Gets the export values of the options selected in a choice field. Its selected indices
+    /// ("I") are used if each of them refers to an option, otherwise its value ("V").
+    fn get_selection(&self, n: usize) -> Vec<String> {
+        let options = self.get_options(n);
+        // "I" takes precedence, but only if every index in it refers to an option
+        let from_indices: Option<Vec<String>> = self
+            .get_inheritable(n, b"I")
+            .and_then(|indices| indices.as_array().ok())
+            .filter(|indices| !indices.is_empty())
+            .and_then(|indices| {
+                indices
+                    .iter()
+                    .map(|index| {
+                        let index = index.resolve(&self.doc).as_i64().ok()?;
+                        if index < 0 {
+                            return None;
+                        }
+                        options.get(index as usize).cloned()
+                    })
+                    .collect()
+            });
+        if let Some(selected) = from_indices {
+            return selected;
+        }
+        let text = |value: &Object| match *value.resolve(&self.doc) {
+            Object::String(ref text, _) => Some(decode_text(text)),
+            Object::Name(ref name) => Some(String::from_utf8_lossy(name).into_owned()),
+            _ => None,
+        };
+        match self.get_inheritable(n, b"V") {
+            Some(Object::Array(values)) => values.iter().filter_map(text).collect(),
+            Some(value) => text(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Gets the export values of the options of a choice field, in the order of its "Opt" array.
+    /// Options given as an `[export display]` pair yield the export value.
+    fn get_options(&self, n: usize) -> Vec<String> {
+        // Kids of a choice field inherit its options
+        let options = match self.get_inheritable(n, b"Opt") {
+            Some(options) => options,
+            None => return Vec::new(),
+        };
+        options
+            .as_array()
+            .map(|options| {
+                options
+                    .iter()
+                    .filter_map(|option| match *option.resolve(&self.doc) {
+                        Object::String(ref text, _) => Some(decode_text(text)),
+                        Object::Array(ref pair) => pair
+                            .first()
+                            .and_then(|export| export.resolve(&self.doc).as_str().ok())
+                            .map(decode_text),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Stamps the widget of the field at index `n` with the current time if modifications are
+    /// being tracked
+    fn touch_field(&mut self, n: usize) {
+        if self.clear_xfa_on_change {
+            self.drop_xfa();
+        }
+        if !self.track_field_modifications {
+            return;
+        }
+        let widget_id = self.get_widget_id(n);
+        if let Some(widget) = self
+            .doc
+            .objects
+            .get_mut(&widget_id)
+            .and_then(|w| w.as_dict_mut().ok())
+        {
+            widget.set(
+                "M",
+                Object::String(pdf_date_now().into_bytes(), StringFormat::Literal),
+            );
+        }
+    }
+
+    /// Collects the key/value pairs of a number tree, such as "PageLabels", in key order
+    fn collect_number_tree<'a>(&'a self, node: &'a Object, entries: &mut Vec<(i64, &'a Object)>) {
+        let node = match node.as_dict() {
+            Ok(node) => node,
+            Err(_) => return,
+        };
+        if let Ok(nums) = node
+            .get(b"Nums")
+            .and_then(|nums| nums.resolve(&self.doc).as_array())
+        {
+            for pair in nums.chunks(2) {
+                if let (Ok(key), Some(value)) = (pair[0].as_i64(), pair.get(1)) {
+                    entries.push((key, value));
+                }
+            }
+        }
+        if let Ok(kids) = node
+            .get(b"Kids")
+            .and_then(|kids| kids.resolve(&self.doc).as_array())
+        {
+            for kid in kids {
+                self.collect_number_tree(kid.resolve(&self.doc), entries);
+            }
+        }
+    }
+
+    /// Sets or clears the bits of `flag` in the field's flags ("Ff"), leaving the others as they
+    /// are
+    fn set_field_flag(&mut self, n: usize, flag: u32, set: bool) {
+        let field = self
+            .doc
+            .objects
+            .get_mut(&self.form_ids[n])
+            .unwrap()
+            .as_dict_mut()
+            .unwrap();
+        // Work on the raw flags so the common ReadOnly/Required/NoExport bits are kept
+        let flags = field.get(b"Ff").and_then(Object::as_i64).unwrap_or(0);
+        let flags = if set {
+            flags | i64::from(flag)
+        } else {
+            flags & !i64::from(flag)
+        };
+        field.set("Ff", Object::Integer(flags));
+    }
+
+    /// Gets the choice field flags of the field
+    fn get_choice_flags(&self, n: usize) -> ChoiceFlags {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        let obj_zero = Object::Integer(0);
+        ChoiceFlags::from_bits_truncate(
+            field.get(b"Ff").unwrap_or(&obj_zero).as_i64().unwrap_or(0) as u32
+        )
+    }
+
+    /// Gets the text field flags of the field
+    fn get_text_flags(&self, n: usize) -> TextFlags {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        let obj_zero = Object::Integer(0);
+        TextFlags::from_bits_truncate(
+            field.get(b"Ff").unwrap_or(&obj_zero).as_i64().unwrap_or(0) as u32
+        )
+    }
+
+    /// Gets the "MaxLen" of the field if it is a comb field, which is the number of cells its text
+    /// is divided into
+    fn get_comb_max_len(&self, n: usize) -> Option<usize> {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        if !self.get_text_flags(n).contains(TextFlags::COMB) {
+            return None;
+        }
+        field
+            .get(b"MaxLen")
+            .and_then(Object::as_i64)
+            .ok()
+            .map(|max_len| max_len as usize)
+    }
+
+    /// Gets the object ids of all of the field's widget annotations. This is the field itself
+    /// when the field and its widget are merged, otherwise its kids.
+    fn get_widget_ids(&self, n: usize) -> Vec<ObjectId> {
+        let field = self
+            .doc
+            .objects
+            .get(&self.form_ids[n])
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        if field.has(b"Rect") {
+            return vec![self.form_ids[n]];
+        }
+        match field.get(b"Kids").and_then(Object::as_array) {
+            Ok(kids) => kids
+                .iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .filter(|id| {
+                    self.doc
+                        .objects
+                        .get(id)
+                        .and_then(|kid| kid.as_dict().ok())
+                        .is_some()
+                })
+                .collect(),
+            Err(_) => vec![self.form_ids[n]],
+        }
+    }
+
+    /// Gets the object id of the field's widget annotation. See `get_widget`.
+    fn get_widget_id(&self, n: usize) -> ObjectId {
+        self.get_widget_ids(n)
+            .first()
+            .cloned()
+            .unwrap_or(self.form_ids[n])
+    }
+
+    /// Builds a new form containing only the page at index `page` and its fields
+    fn extract_page(&self, page: usize) -> Option<Form> {
+        let pages = self.doc.get_pages();
+        let page_id = *pages.values().nth(page)?;
+        let page_widgets: BTreeSet<ObjectId> = self
+            .doc
+            .objects
+            .get(&page_id)?
+            .as_dict()
+            .ok()?
+            .get(b"Annots")
+            .and_then(|annots| annots.resolve(&self.doc).as_array())
+            .map(|annots| {
+                annots
+                    .iter()
+                    .filter_map(|a| a.as_reference().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut form = Form {
+            doc: self.doc.clone(),
+            form_ids: Vec::new(),
+            track_field_modifications: self.track_field_modifications,
+            clear_xfa_on_change: self.clear_xfa_on_change,
+            regenerate_id_on_save: self.regenerate_id_on_save,
+            preserve_appearance_font: self.preserve_appearance_font,
+            embed_values_on_save: self.embed_values_on_save,
+            trim_text: self.trim_text,
+            source_len: None,
+        };
+        let fields = form
+            .get_acroform()?
+            .get(b"Fields")
+            .ok()?
+            .resolve(&form.doc)
+            .as_array()
+            .ok()?
+            .clone();
+        let mut visited = BTreeSet::new();
+        let fields: Vec<Object> = fields
+            .into_iter()
+            .filter(|field| match field.as_reference() {
+                Ok(id) => form.retain_widgets(id, &page_widgets, &mut visited),
+                Err(_) => false,
+            })
+            .collect();
+        form.get_acroform_mut()?
+            .set("Fields", Object::Array(fields));
+
+        let other_pages: Vec<u32> = pages
+            .keys()
+            .cloned()
+            .filter(|&number| number as usize != page + 1)
+            .collect();
+        form.doc.delete_pages(&other_pages);
+        form.doc.prune_objects();
+        // Loading starts from the default options, so carry this form's over again
+        let mut extracted = Form::load_doc(form.doc, &Limits::default()).ok()?;
+        extracted.track_field_modifications = self.track_field_modifications;
+        extracted.clear_xfa_on_change = self.clear_xfa_on_change;
+        extracted.regenerate_id_on_save = self.regenerate_id_on_save;
+        extracted.preserve_appearance_font = self.preserve_appearance_font;
+        extracted.embed_values_on_save = self.embed_values_on_save;
+        extracted.trim_text = self.trim_text;
+        Some(extracted)
+    }
+
+    /// Removes the widgets that are not in `keep` from the field tree rooted at `field_id`, along
+    /// with any fields left without widgets. Returns false if the field itself should be removed.
+    fn retain_widgets(
+        &mut self,
+        field_id: ObjectId,
+        keep: &BTreeSet<ObjectId>,
+        visited: &mut BTreeSet<ObjectId>,
+    ) -> bool {
+        if !visited.insert(field_id) {
+            return false;
+        }
+        let kids = self
+            .doc
+            .objects
+            .get(&field_id)
+            .and_then(|field| field.as_dict().ok())
+            .and_then(|field| field.get(b"Kids").ok())
+            .and_then(|kids| kids.as_array().ok())
+            .cloned();
+        let kids = match kids {
+            Some(kids) => kids,
+            None => return keep.contains(&field_id),
+        };
+        let kids: Vec<Object> = kids
+            .into_iter()
+            .filter(|kid| match kid.as_reference() {
+                Ok(id) => self.retain_widgets(id, keep, visited),
+                Err(_) => false,
+            })
+            .collect();
+        let has_kids = !kids.is_empty();
+        if let Some(field) = self
+            .doc
+            .objects
+            .get_mut(&field_id)
+            .and_then(|field| field.as_dict_mut().ok())
+        {
+            field.set("Kids", Object::Array(kids));
+        }
+        has_kids || keep.contains(&field_id)
+    }
+
+    /// Looks up an inheritable page attribute, walking up the page tree until it is found
+    fn get_inherited_page_attribute(&self, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+        let mut visited = BTreeSet::new();
+        let mut node_id = page_id;
+        while visited.insert(node_id) {
+            let node = self.doc.objects.get(&node_id)?.as_dict().ok()?;
+            if let Ok(value) = node.get(key) {
+                return Some(value.clone());
+            }
+            node_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        }
+        None
+    }
+
+    /// Removes the field from its parent's "Kids", or from the AcroForm "Fields" if it is a top
+    /// level field. The field object itself is left in place.
+    fn detach_field(&mut self, field_id: ObjectId) {
+        let parent_id = self
+            .doc
+            .objects
+            .get(&field_id)
+            .and_then(|field| field.as_dict().ok())
+            .and_then(|field| field.get(b"Parent").ok())
+            .and_then(|parent| parent.as_reference().ok());
+        let siblings = match parent_id {
+            Some(parent_id) => self
+                .doc
+                .objects
+                .get_mut(&parent_id)
+                .and_then(|parent| parent.as_dict_mut().ok())
+                .and_then(|parent| parent.get_mut(b"Kids").ok()),
+            None => self.get_acroform_fields_mut(),
+        };
+        if let Some(&mut Object::Array(ref mut siblings)) = siblings {
+            siblings.retain(|sibling| sibling.as_reference().ok() != Some(field_id));
+        }
+    }
+
+    /// Appends `kid_id` to the "Kids" of the field `parent_id`
+    fn push_kid(&mut self, parent_id: ObjectId, kid_id: ObjectId) {
+        if let Some(parent) = self
+            .doc
+            .objects
+            .get_mut(&parent_id)
+            .and_then(|p| p.as_dict_mut().ok())
+        {
+            if !parent.has(b"Kids") {
+                parent.set("Kids", Object::Array(Vec::new()));
+            }
+            if let Ok(kids) = parent.get_mut(b"Kids").and_then(Object::as_array_mut) {
+                kids.push(Object::Reference(kid_id));
+            }
+        }
+    }
 
+    /// Appends `field_id` to the top level "Fields" of the AcroForm
+    fn push_acroform_field(&mut self, field_id: ObjectId) {
+        if let Some(&mut Object::Array(ref mut fields)) = self.get_acroform_fields_mut() {
+            fields.push(Object::Reference(field_id));
         }
     }
 
+    /// Gets the form's "Fields" array, following the reference if it is kept in an object of its
+    /// own
+    fn get_acroform_fields_mut(&mut self) -> Option<&mut Object> {
+        let fields_id = self
+            .get_acroform()?
+            .get(b"Fields")
+            .and_then(Object::as_reference)
+            .ok();
+        match fields_id {
+            Some(id) => self.doc.objects.get_mut(&id),
+            None => self.get_acroform_mut()?.get_mut(b"Fields").ok(),
+        }
+    }
+
+    /// Looks up an inheritable field attribute, walking up the field's ancestors until it is found
+    fn get_inheritable(&self, n: usize, key: &[u8]) -> Option<&Object> {
+        let node_id = self.get_inheritable_owner(n, key)?;
+        let node = self.doc.objects.get(&node_id)?.as_dict().ok()?;
+        node.get(key).ok().map(|value| value.resolve(&self.doc))
+    }
+
+    /// Finds the field in the chain of parents of field `n` that holds `key`
+    fn get_inheritable_owner(&self, n: usize, key: &[u8]) -> Option<ObjectId> {
+        let mut visited = BTreeSet::new();
+        let mut node_id = self.form_ids[n];
+        while visited.insert(node_id) {
+            let node = self.doc.objects.get(&node_id)?.as_dict().ok()?;
+            if node.has(key) {
+                return Some(node_id);
+            }
+            node_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        }
+        None
+    }
+
+    /// Gets the AcroForm dictionary of the document
+    fn get_acroform(&self) -> Option<&Dictionary> {
+        let catalog = self.doc.catalog().ok()?;
+        catalog
+            .get(b"AcroForm")
+            .ok()?
+            .resolve(&self.doc)
+            .as_dict()
+            .ok()
+    }
+
+    /// Gets the AcroForm dictionary of the document for modification
+    fn get_acroform_mut(&mut self) -> Option<&mut Dictionary> {
+        let root_id = self
+            .doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .ok()?;
+        let acroform_id = self
+            .doc
+            .objects
+            .get(&root_id)?
+            .as_dict()
+            .ok()?
+            .get(b"AcroForm")
+            .and_then(Object::as_reference)
+            .ok();
+        match acroform_id {
+            Some(id) => self.doc.objects.get_mut(&id)?.as_dict_mut().ok(),
+            None => self
+                .doc
+                .objects
+                .get_mut(&root_id)?
+                .as_dict_mut()
+                .ok()?
+                .get_mut(b"AcroForm")
+                .ok()?
+                .as_dict_mut()
+                .ok(),
+        }
+    }
+
+    /// Gets the widget annotation of the field. This is the field itself when the field and its
+    /// widget are merged, otherwise its first kid.
+    fn get_widget(&self, n: usize) -> &Dictionary {
+        // unwraps should be fine because load should have verified everything exists
+        self.doc
+            .objects
+            .get(&self.get_widget_id(n))
+            .unwrap()
+            .as_dict()
+            .unwrap()
+    }
+
+    /// Returns true if the document was saved linearized (optimized for progressive loading over
+    /// the web), which is indicated by a linearization parameter dictionary
+    pub fn is_linearized(&self) -> bool {
+        self.doc.objects.values().any(|obj| match *obj {
+            Object::Dictionary(ref dict) => dict.has(b"Linearized"),
+            _ => false,
+        })
+    }
+
     /// Saves the form to the specified path
+    ///
+    /// The output is never linearized, even if the loaded document was. See `is_linearized`.
     pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
+        self.prepare_save();
         self.doc.save(path).map(|_| ())
     }
 
-    /// Saves the form to the specified path
+    /// Saves the form to the target
+    ///
+    /// Objects are serialized one at a time straight into `target`; the output as a whole is
+    /// never held in memory, so large forms can be written to a socket or pipe without a
+    /// buffer of their size. Writes are not buffered either: wrap unbuffered targets in a
+    /// `BufWriter`.
+    ///
+    /// The output is never linearized, even if the loaded document was. See `is_linearized`.
     pub fn save_to<W: Write>(&mut self, target: &mut W) -> Result<(), io::Error> {
+        self.prepare_save();
         self.doc.save_to(target)
     }
 }
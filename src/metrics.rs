@@ -0,0 +1,96 @@
+//! Character widths of the standard 14 fonts, for laying out text without loading font programs
+//!
+//! The widths come from Adobe's AFM files and are in thousandths of the font size. Only the
+//! printable ASCII range is tabulated; other characters are given the font's typical width.
+
+/// Widths of the characters from space (32) to tilde (126) in Helvetica and Helvetica-Oblique
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Widths of the characters from space (32) to tilde (126) in Helvetica-Bold and
+/// Helvetica-BoldOblique
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Widths of the characters from space (32) to tilde (126) in Times-Roman
+const TIMES_ROMAN: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+/// Widths of the characters from space (32) to tilde (126) in Times-Bold
+const TIMES_BOLD: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500, 930, 722, 667, 722, 722, 667,
+    611, 778, 778, 389, 500, 778, 667, 944, 722, 778, 611, 778, 722, 556, 667, 722, 722, 1000, 722,
+    722, 667, 333, 278, 333, 581, 500, 333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556,
+    278, 833, 556, 500, 556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+/// Widths of the characters from space (32) to tilde (126) in Symbol
+const SYMBOL: [u16; 95] = [
+    250, 333, 713, 500, 549, 833, 778, 439, 333, 333, 500, 549, 250, 549, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 549, 549, 549, 444, 549, 722, 667, 722, 612, 611,
+    763, 603, 722, 333, 631, 722, 686, 889, 722, 722, 768, 741, 556, 592, 611, 690, 439, 768, 645,
+    795, 611, 333, 863, 333, 658, 500, 500, 631, 549, 549, 494, 439, 521, 411, 603, 329, 603, 549,
+    549, 576, 521, 549, 549, 521, 549, 603, 439, 576, 713, 686, 493, 686, 494, 480, 200, 480, 549,
+];
+
+/// Widths of the characters from space (32) to tilde (126) in ZapfDingbats, which holds the
+/// check marks, crosses and circles that check box and radio button appearances draw
+const ZAPF_DINGBATS: [u16; 95] = [
+    278, 974, 961, 974, 980, 719, 789, 790, 791, 690, 960, 939, 549, 855, 911, 933, 911, 945, 974,
+    755, 846, 762, 761, 571, 677, 763, 760, 759, 754, 494, 552, 537, 577, 692, 786, 788, 788, 790,
+    793, 794, 816, 823, 789, 841, 823, 833, 816, 831, 923, 744, 723, 749, 790, 792, 695, 776, 768,
+    792, 759, 707, 708, 682, 701, 826, 815, 789, 789, 707, 687, 696, 689, 786, 787, 713, 791, 785,
+    791, 873, 761, 762, 762, 759, 759, 892, 892, 788, 784, 438, 138, 277, 415, 392, 392, 668, 668,
+];
+
+/// The width of every character in the Courier fonts, which are monospaced
+const COURIER_WIDTH: u16 = 600;
+
+/// Computes the width of `s` when set in the standard font `font` at `size`, in the same unit as
+/// the size. `font` is a base font name such as "Helvetica-Bold". Fonts that aren't one of the
+/// standard 14 are measured as Helvetica, which matches Arial, the most common substitute.
+pub fn text_width(font: &str, size: f64, s: &str) -> f64 {
+    let total: u32 = s.chars().map(|c| char_width(font, c) as u32).sum();
+    f64::from(total) * size / 1000.0
+}
+
+/// Gets the width of a character in thousandths of the font size
+fn char_width(font: &str, c: char) -> u16 {
+    if font.starts_with("Courier") {
+        return COURIER_WIDTH;
+    }
+    // Italic faces are close enough to their upright counterparts for layout purposes
+    let (widths, typical) = if font == "Symbol" {
+        (&SYMBOL, 500)
+    } else if font == "ZapfDingbats" {
+        (&ZAPF_DINGBATS, 788)
+    } else if font.starts_with("Times-Bold") {
+        (&TIMES_BOLD, 500)
+    } else if font.starts_with("Times") {
+        (&TIMES_ROMAN, 500)
+    } else if font.contains("Bold") {
+        (&HELVETICA_BOLD, 611)
+    } else {
+        (&HELVETICA, 556)
+    };
+    match c as u32 {
+        code @ 32..=126 => widths[(code - 32) as usize],
+        _ => typical,
+    }
+}
@@ -0,0 +1,252 @@
+//! Recognition of the Acrobat form functions (AFDate_FormatEx, AFNumber_Keystroke, ...) that
+//! format and keystroke scripts call
+//!
+//! Scripts are not run. Only the first call to a known function is looked at, along with its
+//! literal arguments, which is all that the scripts Acrobat generates consist of.
+
+/// The patterns of `AFDate_Format` and `AFDate_Keystroke`, by their index argument
+#[cfg(feature = "chrono")]
+const DATE_FORMATS: [&str; 14] = [
+    "m/d",
+    "m/d/yy",
+    "mm/dd/yy",
+    "mm/yy",
+    "d-mmm",
+    "d-mmm-yy",
+    "dd-mmm-yy",
+    "yy-mm-dd",
+    "mmm-yy",
+    "mmmm-yy",
+    "mmm d, yyyy",
+    "mmmm d, yyyy",
+    "m/d/yy h:MM tt",
+    "m/d/yy HH:MM",
+];
+
+/// The full English month names, which is what Acrobat formats dates with
+#[cfg(feature = "chrono")]
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Finds the first call to `function` in `script` and returns its arguments, with the quotes
+/// of string literals removed
+fn call_args(script: &str, function: &str) -> Option<Vec<String>> {
+    let start = script.find(&format!("{}(", function))? + function.len() + 1;
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = script[start..].chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) if c == '\\' => current.extend(chars.next()),
+            Some(_) => current.push(c),
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                ',' | ')' => {
+                    let arg = current.trim().to_owned();
+                    if !arg.is_empty() || c == ',' || !args.is_empty() {
+                        args.push(arg);
+                    }
+                    current.clear();
+                    if c == ')' {
+                        return Some(args);
+                    }
+                }
+                _ => current.push(c),
+            },
+        }
+    }
+    None
+}
+
+/// Gets the date pattern, such as "mm/dd/yyyy", that a format or keystroke script formats
+/// dates with
+#[cfg(feature = "chrono")]
+pub fn date_format(script: &str) -> Option<String> {
+    for function in &["AFDate_FormatEx", "AFDate_KeystrokeEx"] {
+        if let Some(args) = call_args(script, function) {
+            return args.into_iter().next();
+        }
+    }
+    for function in &["AFDate_Format", "AFDate_Keystroke"] {
+        if let Some(args) = call_args(script, function) {
+            let index: usize = args.first()?.parse().ok()?;
+            return DATE_FORMATS.get(index).map(|format| (*format).to_owned());
+        }
+    }
+    None
+}
+
+/// Parses `text` as a date laid out by `pattern`, returning the year, month and day. Two digit
+/// years are taken to be from 1950 to 2049. Fails if the pattern has no year, month or day.
+#[cfg(feature = "chrono")]
+pub fn parse_date(text: &str, pattern: &str) -> Option<(i32, u32, u32)> {
+    let (mut year, mut month, mut day) = (None, None, None);
+    let text: Vec<char> = text.trim().chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    while p < pattern.len() {
+        let token = pattern[p];
+        let mut width = 1;
+        while p + width < pattern.len() && pattern[p + width] == token {
+            width += 1;
+        }
+        p += width;
+        if !token.is_ascii_alphabetic() {
+            for _ in 0..width {
+                if t < text.len() && text[t] == token {
+                    t += 1;
+                }
+            }
+            continue;
+        }
+        let letters = text[t..].iter().take_while(|c| c.is_alphabetic()).count();
+        let digits = text[t..].iter().take_while(|c| c.is_ascii_digit()).count();
+        match (token, width) {
+            ('m', 3) | ('m', 4) => {
+                let name: String = text[t..t + letters]
+                    .iter()
+                    .collect::<String>()
+                    .to_lowercase();
+                t += letters;
+                let found = MONTHS.iter().position(|month| {
+                    name.len() >= 3 && (width == 4 && **month == name || month.starts_with(&name))
+                })?;
+                month = Some(found as u32 + 1);
+            }
+            ('d', _) | ('m', _) | ('y', _) => {
+                let max = match (token, width) {
+                    ('y', 4) => 4,
+                    _ => 2,
+                };
+                let digits = digits.min(max);
+                if digits == 0 {
+                    return None;
+                }
+                let number: u32 = text[t..t + digits]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .ok()?;
+                t += digits;
+                match token {
+                    'd' => day = Some(number),
+                    'm' => month = Some(number),
+                    _ if digits <= 2 => {
+                        year = Some(if number < 50 { 2000 } else { 1900 } + number as i32)
+                    }
+                    _ => year = Some(number as i32),
+                }
+            }
+            // Hours, minutes, seconds and AM/PM markers
+            ('t', _) => t += letters,
+            _ => t += digits,
+        }
+    }
+    Some((year?, month?, day?))
+}
+
+/// Checks whether `value` is accepted by the number, percentage and special (zip code, phone
+/// number, social security number and mask) functions that `script` calls, in either their
+/// keystroke or format versions. Returns None if the script calls none of them.
+///
+/// Empty values are always accepted, as Acrobat accepts them.
+pub fn accepts(script: &str, value: &str) -> Option<bool> {
+    let value = value.trim();
+    for &(function, percent) in &[
+        ("AFNumber_Keystroke", false),
+        ("AFNumber_Format", false),
+        ("AFPercent_Keystroke", true),
+        ("AFPercent_Format", true),
+    ] {
+        if let Some(args) = call_args(script, function) {
+            let sep_style = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(0);
+            let currency = args.get(4).map(String::as_str).unwrap_or("");
+            let mut value = value.trim_start_matches('-').trim_start_matches(currency);
+            if percent {
+                value = value.trim_end_matches('%');
+            }
+            let value = value.trim_end_matches(currency).trim();
+            return Some(value.is_empty() || is_number(value, sep_style));
+        }
+    }
+    for function in &["AFSpecial_KeystrokeEx", "AFSpecial_FormatEx"] {
+        if let Some(args) = call_args(script, function) {
+            let mask = args.first().map(String::as_str).unwrap_or("");
+            return Some(value.is_empty() || matches_mask(value, mask));
+        }
+    }
+    for function in &["AFSpecial_Keystroke", "AFSpecial_Format"] {
+        if let Some(args) = call_args(script, function) {
+            let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+            let others = value
+                .chars()
+                .filter(|c| !c.is_ascii_digit())
+                .collect::<String>();
+            let accepted = match args.first().map(String::as_str) {
+                // Zip code
+                Some("0") => digits.len() == 5 && others.is_empty(),
+                // Zip code + 4
+                Some("1") => digits.len() == 9 && others.chars().all(|c| c == '-' || c == ' '),
+                // Phone number, with or without the area code
+                Some("2") => {
+                    (digits.len() == 7 || digits.len() == 10)
+                        && others.chars().all(|c| "()-. ".contains(c))
+                }
+                // Social security number
+                Some("3") => digits.len() == 9 && others.chars().all(|c| c == '-' || c == ' '),
+                _ => return None,
+            };
+            return Some(value.is_empty() || accepted);
+        }
+    }
+    None
+}
+
+/// Checks whether `value` is a number written in the separator style of `AFNumber_Keystroke`:
+/// 0 is "1,234.56", 1 is "1234.56", 2 is "1.234,56", 3 is "1234,56" and 4 is "1'234.56"
+fn is_number(value: &str, sep_style: u32) -> bool {
+    let (group, decimal) = match sep_style {
+        1 => (None, '.'),
+        2 => (Some('.'), ','),
+        3 => (None, ','),
+        4 => (Some('\''), '.'),
+        _ => (Some(','), '.'),
+    };
+    let mut parts = value.splitn(2, decimal);
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let whole_ok = whole
+        .chars()
+        .all(|c| c.is_ascii_digit() || Some(c) == group);
+    whole_ok
+        && fraction.chars().all(|c| c.is_ascii_digit())
+        && value.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Checks `value` against an `AFSpecial_KeystrokeEx` mask, in which "9" stands for a digit,
+/// "A" for a letter, "O" for a letter or digit, "X" for any character and everything else for
+/// itself
+fn matches_mask(value: &str, mask: &str) -> bool {
+    value.chars().count() == mask.chars().count()
+        && value.chars().zip(mask.chars()).all(|(c, m)| match m {
+            '9' => c.is_ascii_digit(),
+            'A' => c.is_alphabetic(),
+            'O' => c.is_alphanumeric(),
+            'X' => true,
+            _ => c == m,
+        })
+}
@@ -0,0 +1,243 @@
+//! Serialization of documents with compressed object streams and a cross-reference stream
+//!
+//! lopdf writes every object on its own with a plain cross-reference table, which is much larger
+//! than the object streams (PDF 1.5) most modern forms are saved with. This packs every object it
+//! can into object streams instead.
+
+use lopdf::{Dictionary, Document, Object, Stream, StringFormat};
+use std::io::{self, Write};
+
+/// The most objects put in a single object stream
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// Where an object ended up in the output, for the cross-reference stream
+enum Location {
+    /// At a byte offset in the file, with the given generation number
+    Offset(u64, u16),
+    /// At an index within the object stream with the given object number
+    Compressed(u32, usize),
+}
+
+/// Writes the document to `target` using object streams and a cross-reference stream
+pub fn write_compressed<W: Write>(doc: &Document, target: &mut W) -> io::Result<()> {
+    let mut out = CountingWrite {
+        inner: target,
+        written: 0,
+    };
+    let version = if doc.version.as_str() < "1.5" {
+        "1.5"
+    } else {
+        doc.version.as_str()
+    };
+    writeln!(out, "%PDF-{}", version)?;
+    // Bytes above 127 mark the file as binary for tools that guess from its start
+    out.write_all(b"%\xE2\xE3\xCF\xD3\n")?;
+
+    let mut locations: Vec<(u32, Location)> = Vec::new();
+    let mut packable = Vec::new();
+    for (&(id, generation), object) in &doc.objects {
+        let skip = object
+            .type_name()
+            .map(|name| name == "ObjStm" || name == "XRef")
+            .unwrap_or(false);
+        if skip {
+            continue;
+        }
+        // Streams, and objects that aren't of generation 0, can't go into object streams
+        match *object {
+            Object::Stream(_) => (),
+            _ if generation == 0 => {
+                packable.push((id, object));
+                continue;
+            }
+            _ => (),
+        }
+        locations.push((id, Location::Offset(out.written, generation)));
+        writeln!(out, "{} {} obj", id, generation)?;
+        out.write_all(&serialize(object))?;
+        out.write_all(b"\nendobj\n")?;
+    }
+
+    let mut next_id = doc.max_id + 1;
+    for chunk in packable.chunks(OBJECTS_PER_STREAM) {
+        let stream_id = next_id;
+        next_id += 1;
+        let mut header = Vec::new();
+        let mut body = Vec::new();
+        for (index, &(id, object)) in chunk.iter().enumerate() {
+            write!(header, "{} {} ", id, body.len())?;
+            body.extend_from_slice(&serialize(object));
+            body.push(b'\n');
+            locations.push((id, Location::Compressed(stream_id, index)));
+        }
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"ObjStm".to_vec()));
+        dict.set("N", Object::Integer(chunk.len() as i64));
+        dict.set("First", Object::Integer(header.len() as i64));
+        header.extend_from_slice(&body);
+        let mut stream = Stream::new(dict, header);
+        let _ = stream.compress();
+        locations.push((stream_id, Location::Offset(out.written, 0)));
+        writeln!(out, "{} 0 obj", stream_id)?;
+        out.write_all(&serialize(&Object::Stream(stream)))?;
+        out.write_all(b"\nendobj\n")?;
+    }
+
+    // The cross-reference stream lists itself too
+    let xref_id = next_id;
+    let xref_offset = out.written;
+    locations.push((xref_id, Location::Offset(xref_offset, 0)));
+    let size = xref_id + 1;
+    // The second field holds offsets and object stream numbers, so it is made as wide as the
+    // largest of them needs
+    let largest = locations
+        .iter()
+        .map(|(_, location)| match *location {
+            Location::Offset(offset, _) => offset,
+            Location::Compressed(stream_id, _) => u64::from(stream_id),
+        })
+        .max()
+        .unwrap_or(0);
+    let width = (8 - largest.leading_zeros() as usize / 8).max(1);
+    let entry_len = 1 + width + 2;
+    let mut entries = vec![0u8; size as usize * entry_len];
+    // Object 0 is the head of the free list
+    entries[entry_len - 2] = 0xFF;
+    entries[entry_len - 1] = 0xFF;
+    for (id, location) in locations {
+        let start = id as usize * entry_len;
+        let entry = &mut entries[start..start + entry_len];
+        let (kind, field2, field3) = match location {
+            Location::Offset(offset, generation) => (1, offset, generation),
+            Location::Compressed(stream_id, index) => (2, u64::from(stream_id), index as u16),
+        };
+        entry[0] = kind;
+        for (i, byte) in entry[1..=width].iter_mut().enumerate() {
+            *byte = (field2 >> (8 * (width - 1 - i))) as u8;
+        }
+        entry[width + 1..].copy_from_slice(&[(field3 >> 8) as u8, field3 as u8]);
+    }
+    let mut dict = Dictionary::new();
+    for (key, value) in doc.trailer.iter() {
+        if key != b"Prev" && key != b"XRefStm" && key != b"Size" {
+            dict.set(key.clone(), value.clone());
+        }
+    }
+    dict.set("Type", Object::Name(b"XRef".to_vec()));
+    dict.set("Size", Object::Integer(i64::from(size)));
+    dict.set(
+        "W",
+        Object::Array(vec![
+            Object::Integer(1),
+            Object::Integer(width as i64),
+            Object::Integer(2),
+        ]),
+    );
+    let mut stream = Stream::new(dict, entries);
+    let _ = stream.compress();
+    writeln!(out, "{} 0 obj", xref_id)?;
+    out.write_all(&serialize(&Object::Stream(stream)))?;
+    out.write_all(b"\nendobj\n")?;
+    write!(out, "startxref\n{}\n%%EOF", xref_offset)?;
+    Ok(())
+}
+
+/// Serializes a direct object in PDF syntax
+fn serialize(object: &Object) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_object(&mut out, object);
+    out
+}
+
+fn write_object(out: &mut Vec<u8>, object: &Object) {
+    match *object {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(value) => out.extend_from_slice(if value { b"true" } else { b"false" }),
+        Object::Integer(value) => out.extend_from_slice(value.to_string().as_bytes()),
+        Object::Real(value) => out.extend_from_slice(value.to_string().as_bytes()),
+        Object::Name(ref name) => write_name(out, name),
+        Object::String(ref text, StringFormat::Literal) => {
+            out.push(b'(');
+            for &byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => {
+                        out.push(b'\\');
+                        out.push(byte);
+                    }
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    _ => out.push(byte),
+                }
+            }
+            out.push(b')');
+        }
+        Object::String(ref text, StringFormat::Hexadecimal) => {
+            out.push(b'<');
+            for byte in text {
+                out.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            out.push(b'>');
+        }
+        Object::Array(ref array) => {
+            out.push(b'[');
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        Object::Dictionary(ref dict) => write_dictionary(out, dict),
+        Object::Stream(ref stream) => {
+            let mut dict = stream.dict.clone();
+            dict.set("Length", Object::Integer(stream.content.len() as i64));
+            write_dictionary(out, &dict);
+            out.extend_from_slice(b"stream\n");
+            out.extend_from_slice(&stream.content);
+            out.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference((id, generation)) => {
+            out.extend_from_slice(format!("{} {} R", id, generation).as_bytes())
+        }
+    }
+}
+
+fn write_dictionary(out: &mut Vec<u8>, dict: &Dictionary) {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        write_name(out, key);
+        out.push(b' ');
+        write_object(out, value);
+    }
+    out.extend_from_slice(b">>");
+}
+
+/// Writes a name, escaping bytes that are delimiters or not printable as `#xx`
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for &byte in name {
+        if !(0x21..=0x7E).contains(&byte) || b"#()<>[]{}/%".contains(&byte) {
+            out.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+        } else {
+            out.push(byte);
+        }
+    }
+}
+
+/// Counts the bytes written so objects' offsets are known
+struct CountingWrite<'a, W: Write + 'a> {
+    inner: &'a mut W,
+    written: u64,
+}
+
+impl<'a, W: Write> Write for CountingWrite<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
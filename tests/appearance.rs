@@ -0,0 +1,368 @@
+extern crate lopdf;
+extern crate pdf_form_ids;
+
+mod common;
+
+use common::*;
+use lopdf::{Dictionary, Document, Object, Stream};
+use pdf_form_ids::*;
+
+#[test]
+fn reads_down_and_rollover_appearances() {
+    let mut pdf = TestPdf::new(1);
+    let normal = pdf.add_stream(b"normal");
+    let down = pdf.add_stream(b"down");
+    let mut appearances = Dictionary::new();
+    appearances.set("N", normal);
+    appearances.set("D", down);
+    let mut field = text_field("name");
+    field.set("AP", Object::Dictionary(appearances));
+    pdf.add_field(0, field);
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_appearance_stream(0), Some(b"normal".to_vec()));
+    assert_eq!(
+        form.get_appearance_stream_variant(0, AppearanceVariant::Down),
+        Some(b"down".to_vec())
+    );
+    assert_eq!(
+        form.get_appearance_stream_variant(0, AppearanceVariant::Rollover),
+        None
+    );
+}
+
+#[test]
+fn positions_generated_text_by_quadding() {
+    let mut pdf = TestPdf::new(1);
+    for &(title, quadding) in &[("left", 0), ("center", 1), ("right", 2)] {
+        let mut field = text_field(title);
+        field.set("Q", quadding as i64);
+        pdf.add_field(0, field);
+    }
+    let mut form = pdf.into_form();
+
+    // Fields are 100 wide with a padding of 2, so the centered text lies halfway between the
+    // left and right aligned text, whatever its width
+    let mut x = Vec::new();
+    for n in 0..3 {
+        form.set_text(n, "Hi".to_owned()).unwrap();
+        form.generate_appearance(n).unwrap();
+        let content = appearance_of(&form, n);
+        let line = content.lines().find(|line| line.ends_with(" Td")).unwrap();
+        x.push(line.split(' ').next().unwrap().parse::<f64>().unwrap());
+    }
+    assert_eq!(x[0], 2.0);
+    assert!(x[1] > x[0] && x[2] > x[1] && x[2] < 98.0);
+    assert!((x[0] + x[2] - 2.0 * x[1]).abs() < 0.02);
+}
+
+#[test]
+fn measures_text_with_the_font_metrics() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_font("Cour", "Courier");
+    pdf.add_font("TiRo", "Times-Roman");
+    for &(title, font) in &[
+        ("helvetica", "Helv"),
+        ("courier", "Cour"),
+        ("times", "TiRo"),
+    ] {
+        let mut field = text_field(title);
+        field.set("Q", 2i64);
+        field.set("DA", string(&format!("/{} 10 Tf 0 g", font)));
+        pdf.add_field(0, field);
+    }
+    let mut form = pdf.into_form();
+    for n in 0..3 {
+        form.set_text(n, "Wide".to_owned()).unwrap();
+        form.generate_appearance(n).unwrap();
+    }
+
+    // Right justified text starts its width plus the padding of 2 from the right edge at 100
+    assert!(appearance_of(&form, 0).contains("\n75.22 "));
+    assert!(appearance_of(&form, 1).contains("\n74 "));
+    assert!(appearance_of(&form, 2).contains("\n76.34 "));
+}
+
+#[test]
+fn wraps_multiline_text_to_the_field_width() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_font("Cour", "Courier");
+    let mut field = text_field("notes");
+    field.set("Ff", 0x1000i64);
+    field.set("DA", string("/Cour 10 Tf 0 g"));
+    field.set("Rect", rect([0, 0, 100, 60]));
+    pdf.add_field(0, field);
+    let mut form = pdf.into_form();
+
+    // Lines are 96 wide inside the padding, which fits 16 Courier characters at 10 points
+    form.set_text(
+        0,
+        "the quick brown fox jumps over the lazy dog\nend".to_owned(),
+    )
+    .unwrap();
+    form.generate_appearance(0).unwrap();
+    assert_eq!(
+        shown_lines(&appearance_of(&form, 0)),
+        vec!["the quick brown", "fox jumps over", "the lazy dog", "end"]
+    );
+
+    form.set_text(0, "abcdefghijklmnopqrstuvwxyz".to_owned())
+        .unwrap();
+    form.generate_appearance(0).unwrap();
+    assert_eq!(
+        shown_lines(&appearance_of(&form, 0)),
+        vec!["abcdefghijklmnop", "qrstuvwxyz"]
+    );
+}
+
+#[test]
+fn reads_and_writes_cmyk_text_colors() {
+    let mut pdf = TestPdf::new(1);
+    let mut field = text_field("name");
+    field.set("DA", string("/Helv 10 Tf 0 0 1 rg"));
+    pdf.add_field(0, field);
+    let mut form = pdf.into_form();
+    assert_eq!(
+        form.get_appearance_settings(0),
+        Some(AppearanceSettings {
+            font: "Helv".to_owned(),
+            font_size: 10.0,
+            color: Some(Color::Rgb(0.0, 0.0, 1.0)),
+        })
+    );
+
+    form.set_text_color(0, Color::Cmyk(0.1, 0.2, 0.3, 0.4))
+        .unwrap();
+    let settings = form.get_appearance_settings(0).unwrap();
+    assert_eq!(settings.color, Some(Color::Cmyk(0.1, 0.2, 0.3, 0.4)));
+    assert_eq!(settings.font_size, 10.0);
+    form.set_text(0, "Ann".to_owned()).unwrap();
+    form.generate_appearance(0).unwrap();
+    assert!(appearance_of(&form, 0).contains("0.1 0.2 0.3 0.4 k"));
+}
+
+#[test]
+fn reads_the_widget_background() {
+    let mut pdf = TestPdf::new(1);
+    let backgrounds = vec![
+        name("Sh0"),
+        Object::Array(vec![
+            Object::Integer(1),
+            Object::Integer(0),
+            Object::Integer(0),
+        ]),
+        Object::Array(vec![Object::Integer(1)]),
+    ];
+    for (i, background) in backgrounds.into_iter().enumerate() {
+        let mut characteristics = Dictionary::new();
+        characteristics.set("BG", background);
+        let mut field = text_field(&format!("field{}", i));
+        field.set("MK", Object::Dictionary(characteristics));
+        pdf.add_field(0, field);
+    }
+    pdf.add_field(0, text_field("plain"));
+    let form = pdf.into_form();
+
+    assert_eq!(
+        form.get_widget_background(0),
+        Some(WidgetBackground::Pattern)
+    );
+    assert_eq!(
+        form.get_widget_background(1),
+        Some(WidgetBackground::Color(Color::Rgb(1.0, 0.0, 0.0)))
+    );
+    assert_eq!(
+        form.get_widget_background(2),
+        Some(WidgetBackground::Color(Color::Gray(1.0)))
+    );
+    assert_eq!(form.get_widget_background(3), None);
+}
+
+#[test]
+fn keeps_the_appearance_font_when_setting_text() {
+    let mut pdf = TestPdf::new(1);
+    let mut stream_dict = Dictionary::new();
+    stream_dict.set("BBox", rect([0, 0, 100, 20]));
+    let old = pdf.doc.add_object(Stream::new(
+        stream_dict,
+        b"/Tx BMC BT /Helv 7 Tf 1 0 0 rg 2 5 Td (old) Tj ET EMC".to_vec(),
+    ));
+    let mut appearances = Dictionary::new();
+    appearances.set("N", old);
+    let mut field = text_field("name");
+    field.set("AP", Object::Dictionary(appearances));
+    pdf.add_field(0, field.clone());
+    field.set("T", string("other"));
+    pdf.add_field(0, field);
+    let mut form = pdf.into_form();
+
+    form.set_text(0, "dropped".to_owned()).unwrap();
+    assert_eq!(form.get_appearance_stream(0), None);
+    form.set_preserve_appearance_font(true);
+    form.set_text(1, "new".to_owned()).unwrap();
+    let content = appearance_of(&form, 1);
+    assert!(content.contains("/Helv 7 Tf"), "{}", content);
+    assert!(content.contains("1 0 0 rg"), "{}", content);
+    assert_eq!(shown_lines(&content), vec!["new"]);
+}
+
+#[test]
+fn checks_that_the_default_appearance_font_exists() {
+    let mut pdf = TestPdf::new(1);
+    for &(title, da) in &[
+        ("missing", "/Missing 10 Tf 0 g"),
+        ("standard", "/Courier 10 Tf 0 g"),
+        ("resource", "/Helv 10 Tf 0 g"),
+        ("none", "0 g"),
+    ] {
+        let mut field = text_field(title);
+        field.set("DA", string(da));
+        pdf.add_field(0, field);
+    }
+    let form = pdf.into_form();
+
+    assert!(!form.da_font_available(0));
+    assert!(form.da_font_available(1));
+    assert!(form.da_font_available(2));
+    assert!(!form.da_font_available(3));
+}
+
+#[test]
+fn repairs_a_missing_default_appearance() {
+    let mut pdf = TestPdf::new(1);
+    pdf.acroform.remove(b"DA");
+    let mut field = text_field("name");
+    field.remove(b"DA");
+    pdf.add_field(0, field);
+    pdf.add_field(0, text_field("city"));
+    let mut form = pdf.into_form();
+
+    form.set_text(0, "Ann".to_owned()).unwrap();
+    assert!(matches!(
+        form.generate_appearance(0),
+        Err(ValueError::MissingDefaultAppearance)
+    ));
+    form.ensure_da(0, "/Helv 0 Tf 0 g");
+    form.ensure_da(1, "/Cour 8 Tf 0 g");
+    assert_eq!(form.get_appearance_settings(0).unwrap().font, "Helv");
+    assert_eq!(form.get_appearance_settings(1).unwrap().font_size, 12.0);
+    form.generate_appearance(0).unwrap();
+    assert_eq!(shown_lines(&appearance_of(&form, 0)), vec!["Ann"]);
+}
+
+#[test]
+fn sets_text_and_renders_only_that_field() {
+    let mut form = text_form(&["name", "city"]);
+
+    form.set_text_and_render(0, "Hello there".to_owned())
+        .unwrap();
+    form.set_text(1, "Oslo".to_owned()).unwrap();
+    assert_eq!(text_of(&form, 0), "Hello there");
+    assert_eq!(shown_lines(&appearance_of(&form, 0)), vec!["Hello there"]);
+    assert_eq!(form.get_appearance_stream(1), None);
+}
+
+#[test]
+fn lists_stale_appearances() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    pdf.add_field(0, text_field("notes"));
+    let mut form = pdf.into_form();
+
+    assert!(form.stale_appearances().is_empty());
+    form.set_text(0, "x".to_owned()).unwrap();
+    form.set_text(1, "y".to_owned()).unwrap();
+    assert_eq!(form.stale_appearances(), vec![0, 1]);
+    form.generate_appearance(0).unwrap();
+    assert_eq!(form.stale_appearances(), vec![1]);
+}
+
+#[test]
+fn finalizes_the_need_appearances_flag() {
+    fn need_appearances(form: &mut Form) -> bool {
+        let doc = saved_doc(form);
+        let acroform = doc
+            .catalog()
+            .unwrap()
+            .get(b"AcroForm")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let acroform = doc.get_object(acroform).unwrap().as_dict().unwrap();
+        match acroform.get(b"NeedAppearances") {
+            Ok(&Object::Boolean(need)) => need,
+            _ => false,
+        }
+    }
+
+    let mut form = text_form(&["name"]);
+    form.finalize();
+    assert!(!need_appearances(&mut form));
+    form.set_text(0, "x".to_owned()).unwrap();
+    form.finalize();
+    assert!(need_appearances(&mut form));
+    // Once set, the flag is left for `render_all_appearances` to clear
+    form.generate_appearance(0).unwrap();
+    form.finalize();
+    assert!(need_appearances(&mut form));
+}
+
+#[test]
+fn sets_the_border_dash_pattern() {
+    let mut form = text_form(&["name"]);
+    assert_eq!(form.get_border_dash(0), None);
+
+    form.set_border_dash(0, &[3.0, 2.0]);
+    let mut form = reload(&mut form);
+    assert_eq!(form.get_border_dash(0), Some(vec![3.0, 2.0]));
+    form.set_border_dash(0, &[]);
+    assert_eq!(form.get_border_dash(0), None);
+}
+
+#[test]
+fn renders_every_appearance() {
+    fn need_appearances(form: &mut Form) -> Option<bool> {
+        let doc = saved_doc(form);
+        let acroform = doc
+            .catalog()
+            .unwrap()
+            .get(b"AcroForm")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let acroform = doc.get_object(acroform).unwrap().as_dict().unwrap();
+        match acroform.get(b"NeedAppearances") {
+            Ok(&Object::Boolean(need)) => Some(need),
+            _ => None,
+        }
+    }
+
+    let mut pdf = TestPdf::new(1);
+    pdf.acroform.set("NeedAppearances", true);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    pdf.add_field(0, check_box("agree", "On"));
+    let stale = pdf.add_field(0, check_box("stale", "Yes"));
+    let bytes = pdf.into_bytes();
+
+    let mut form = Form::load_from(&bytes[..]).unwrap();
+    form.apply_lines("name=one\ncity=two\nagree=yes").unwrap();
+    assert_eq!(form.stale_appearances(), vec![0, 1]);
+    form.render_all_appearances().unwrap();
+    assert!(form.stale_appearances().is_empty());
+    assert_eq!(need_appearances(&mut form), Some(false));
+
+    // A check box showing a state it has no appearance for still needs the viewer
+    let mut doc = Document::load_mem(&bytes).unwrap();
+    let widget = doc.get_object_mut(stale).unwrap().as_dict_mut().unwrap();
+    widget.set("AS", name("On"));
+    widget.set("V", name("On"));
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    let mut form = Form::load_from(&bytes[..]).unwrap();
+    assert_eq!(form.stale_appearances(), vec![3]);
+    form.render_all_appearances().unwrap();
+    assert_eq!(form.stale_appearances(), vec![3]);
+    assert_eq!(need_appearances(&mut form), Some(true));
+}
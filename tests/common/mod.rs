@@ -0,0 +1,339 @@
+//! Builds small documents with forms for the integration tests
+#![allow(dead_code)]
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use pdf_form_ids::{FieldState, Form};
+
+pub fn string(text: &str) -> Object {
+    Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+}
+
+pub fn name(text: &str) -> Object {
+    Object::Name(text.as_bytes().to_vec())
+}
+
+pub fn rect(coords: [i64; 4]) -> Object {
+    Object::Array(coords.iter().map(|&c| Object::Integer(c)).collect())
+}
+
+pub fn strings(items: &[&str]) -> Object {
+    Object::Array(items.iter().map(|item| string(item)).collect())
+}
+
+/// A text field with a default appearance
+pub fn text_field(title: &str) -> Dictionary {
+    let mut field = Dictionary::new();
+    field.set("FT", name("Tx"));
+    field.set("T", string(title));
+    field.set("DA", string("/Helv 12 Tf 0 g"));
+    field
+}
+
+/// An unchecked check box whose normal appearance has the states `on_state` and "Off"
+pub fn check_box(title: &str, on_state: &str) -> Dictionary {
+    let mut states = Dictionary::new();
+    states.set(on_state, Object::Null);
+    states.set("Off", Object::Null);
+    let mut appearances = Dictionary::new();
+    appearances.set("N", Object::Dictionary(states));
+    let mut field = Dictionary::new();
+    field.set("FT", name("Btn"));
+    field.set("T", string(title));
+    field.set("AP", Object::Dictionary(appearances));
+    field.set("AS", name("Off"));
+    field
+}
+
+/// A choice field with the field flags `flags`
+pub fn choice(title: &str, flags: i64, options: &[&str]) -> Dictionary {
+    let mut field = Dictionary::new();
+    field.set("FT", name("Ch"));
+    field.set("T", string(title));
+    field.set("Ff", flags);
+    field.set("Opt", strings(options));
+    field.set("DA", string("/Helv 12 Tf 0 g"));
+    field
+}
+
+/// A document of blank letter sized pages, with a form using Helvetica as "Helv"
+pub struct TestPdf {
+    pub doc: Document,
+    pub catalog: Dictionary,
+    pub acroform: Dictionary,
+    pages: Vec<ObjectId>,
+    pub fields: Vec<Object>,
+}
+
+impl TestPdf {
+    pub fn new(page_count: usize) -> Self {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let contents = doc.add_object(Stream::new(Dictionary::new(), Vec::new()));
+        let mut pages = Vec::new();
+        for _ in 0..page_count {
+            let mut page = Dictionary::new();
+            page.set("Type", name("Page"));
+            page.set("Parent", pages_id);
+            page.set("MediaBox", rect([0, 0, 612, 792]));
+            page.set("Contents", contents);
+            page.set("Annots", Object::Array(Vec::new()));
+            pages.push(doc.add_object(page));
+        }
+        let mut tree = Dictionary::new();
+        tree.set("Type", name("Pages"));
+        tree.set(
+            "Kids",
+            Object::Array(pages.iter().map(|&id| Object::Reference(id)).collect()),
+        );
+        tree.set("Count", page_count as i64);
+        doc.objects.insert(pages_id, Object::Dictionary(tree));
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", name("Catalog"));
+        catalog.set("Pages", pages_id);
+
+        let mut helvetica = Dictionary::new();
+        helvetica.set("Type", name("Font"));
+        helvetica.set("Subtype", name("Type1"));
+        helvetica.set("BaseFont", name("Helvetica"));
+        let mut fonts = Dictionary::new();
+        fonts.set("Helv", Object::Dictionary(helvetica));
+        let mut resources = Dictionary::new();
+        resources.set("Font", Object::Dictionary(fonts));
+        let mut acroform = Dictionary::new();
+        acroform.set("DR", Object::Dictionary(resources));
+        acroform.set("DA", string("/Helv 0 Tf 0 g"));
+
+        TestPdf {
+            doc,
+            catalog,
+            acroform,
+            pages,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn page(&self, index: usize) -> ObjectId {
+        self.pages[index]
+    }
+
+    pub fn dict_mut(&mut self, id: ObjectId) -> &mut Dictionary {
+        self.doc.get_object_mut(id).unwrap().as_dict_mut().unwrap()
+    }
+
+    pub fn add_stream(&mut self, content: &[u8]) -> ObjectId {
+        self.doc
+            .add_object(Stream::new(Dictionary::new(), content.to_vec()))
+    }
+
+    /// Adds `field` as a top level field that is also its own widget on `page`. It gets a default
+    /// "Rect" unless it has one.
+    pub fn add_field(&mut self, page: usize, mut field: Dictionary) -> ObjectId {
+        let page_id = self.pages[page];
+        field.set("Type", name("Annot"));
+        field.set("Subtype", name("Widget"));
+        field.set("P", page_id);
+        if !field.has(b"Rect") {
+            field.set("Rect", rect([10, 10, 110, 30]));
+        }
+        let id = self.doc.add_object(field);
+        self.add_annotation(page, Object::Reference(id));
+        self.fields.push(Object::Reference(id));
+        id
+    }
+
+    /// Adds a radio button group to `page` with a widget for each of `states`, with `selected`
+    /// turned on. Returns the group's field.
+    pub fn add_radio(
+        &mut self,
+        page: usize,
+        title: &str,
+        states: &[&str],
+        selected: Option<&str>,
+    ) -> ObjectId {
+        let group_id = self.doc.new_object_id();
+        let mut kids = Vec::new();
+        for (i, &state) in states.iter().enumerate() {
+            let mut normal = Dictionary::new();
+            normal.set(state, Object::Null);
+            normal.set("Off", Object::Null);
+            let mut appearances = Dictionary::new();
+            appearances.set("N", Object::Dictionary(normal));
+            let mut widget = Dictionary::new();
+            widget.set("Type", name("Annot"));
+            widget.set("Subtype", name("Widget"));
+            widget.set("Parent", group_id);
+            widget.set("P", self.pages[page]);
+            let left = 10 + 20 * i as i64;
+            widget.set("Rect", rect([left, 100, left + 10, 110]));
+            widget.set("AP", Object::Dictionary(appearances));
+            let state = if selected == Some(state) {
+                state
+            } else {
+                "Off"
+            };
+            widget.set("AS", name(state));
+            let widget_id = self.doc.add_object(widget);
+            self.add_annotation(page, Object::Reference(widget_id));
+            kids.push(Object::Reference(widget_id));
+        }
+        let mut group = Dictionary::new();
+        group.set("FT", name("Btn"));
+        group.set("Ff", 0xC000i64);
+        group.set("T", string(title));
+        group.set("Kids", Object::Array(kids));
+        if let Some(state) = selected {
+            group.set("V", name(state));
+        }
+        self.doc.objects.insert(group_id, Object::Dictionary(group));
+        self.fields.push(Object::Reference(group_id));
+        group_id
+    }
+
+    /// Adds a field with no type of its own, which groups the fields added to it with `add_kid`
+    pub fn add_group(&mut self, title: &str) -> ObjectId {
+        let mut group = Dictionary::new();
+        group.set("T", string(title));
+        group.set("Kids", Object::Array(Vec::new()));
+        let id = self.doc.add_object(group);
+        self.fields.push(Object::Reference(id));
+        id
+    }
+
+    /// Adds `field` to `page` like `add_field`, but as a kid of `parent` instead of at the top
+    /// level
+    pub fn add_kid(&mut self, page: usize, parent: ObjectId, mut field: Dictionary) -> ObjectId {
+        field.set("Parent", parent);
+        let id = self.add_field(page, field);
+        self.fields.pop();
+        self.dict_mut(parent)
+            .get_mut(b"Kids")
+            .unwrap()
+            .as_array_mut()
+            .unwrap()
+            .push(Object::Reference(id));
+        id
+    }
+
+    /// Adds a standard font to the form's resources under the name `resource`
+    pub fn add_font(&mut self, resource: &str, base_font: &str) {
+        let mut font = Dictionary::new();
+        font.set("Type", name("Font"));
+        font.set("Subtype", name("Type1"));
+        font.set("BaseFont", name(base_font));
+        let resources = self.acroform.get_mut(b"DR").unwrap().as_dict_mut().unwrap();
+        let fonts = resources.get_mut(b"Font").unwrap().as_dict_mut().unwrap();
+        fonts.set(resource, Object::Dictionary(font));
+    }
+
+    /// Adds an annotation that is not a field to `page`
+    pub fn add_annotation(&mut self, page: usize, annotation: Object) {
+        let page_id = self.pages[page];
+        self.dict_mut(page_id)
+            .get_mut(b"Annots")
+            .unwrap()
+            .as_array_mut()
+            .unwrap()
+            .push(annotation);
+    }
+
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        self.acroform.set("Fields", Object::Array(self.fields));
+        let acroform_id = self.doc.add_object(self.acroform);
+        self.catalog.set("AcroForm", acroform_id);
+        let catalog_id = self.doc.add_object(self.catalog);
+        self.doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        self.doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    pub fn into_form(self) -> Form {
+        Form::load_from(&self.into_bytes()[..]).unwrap()
+    }
+}
+
+/// A form with a text field on the first page for each name
+pub fn text_form(titles: &[&str]) -> Form {
+    let mut pdf = TestPdf::new(1);
+    for title in titles {
+        pdf.add_field(0, text_field(title));
+    }
+    pdf.into_form()
+}
+
+pub fn save(form: &mut Form) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    form.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// Saves the form and parses the output with lopdf, to look at what was written
+pub fn saved_doc(form: &mut Form) -> Document {
+    Document::load_mem(&save(form)).unwrap()
+}
+
+pub fn saved_dict(form: &mut Form, id: ObjectId) -> Dictionary {
+    let doc = saved_doc(form);
+    doc.get_object(id).unwrap().as_dict().unwrap().clone()
+}
+
+/// Saves the form and gets the dictionary of the field named `title` from the output
+pub fn saved_field(form: &mut Form, title: &str) -> Dictionary {
+    let doc = saved_doc(form);
+    let field = doc
+        .objects
+        .values()
+        .filter_map(|object| object.as_dict().ok())
+        .find(|dict| dict.get(b"T").and_then(Object::as_str).ok() == Some(title.as_bytes()))
+        .unwrap();
+    field.clone()
+}
+
+/// The text value ("V") the field named `title` is saved with
+pub fn saved_text(form: &mut Form, title: &str) -> Option<String> {
+    saved_field(form, title)
+        .get(b"V")
+        .and_then(Object::as_str)
+        .ok()
+        .map(|text| String::from_utf8_lossy(text).into_owned())
+}
+
+pub fn reload(form: &mut Form) -> Form {
+    Form::load_from(&save(form)[..]).unwrap()
+}
+
+pub fn text_of(form: &Form, n: usize) -> String {
+    match form.get_state(n) {
+        FieldState::Text { text } => text,
+        state => panic!("field {} is not a text field: {:?}", n, state),
+    }
+}
+
+pub fn is_checked(form: &Form, n: usize) -> bool {
+    match form.get_state(n) {
+        FieldState::CheckBox { is_checked } => is_checked,
+        state => panic!("field {} is not a check box: {:?}", n, state),
+    }
+}
+
+/// The normal appearance stream of the field, as text
+pub fn appearance_of(form: &Form, n: usize) -> String {
+    String::from_utf8(form.get_appearance_stream(n).unwrap()).unwrap()
+}
+
+/// The strings shown with `Tj` in an appearance stream, one per line of text
+pub fn shown_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| line.ends_with(" Tj"))
+        .map(|line| line[1..line.len() - 4].to_owned())
+        .collect()
+}
+
+/// The index of the field with the full name `title`
+pub fn field_index(form: &Form, title: &str) -> usize {
+    (0..form.len())
+        .find(|&n| form.get_full_name(n).as_deref() == Some(title))
+        .unwrap()
+}
@@ -0,0 +1,827 @@
+extern crate lopdf;
+extern crate pdf_form_ids;
+
+mod common;
+
+use common::*;
+use lopdf::{Dictionary, Document, Object, Stream, StringFormat};
+use pdf_form_ids::*;
+use std::collections::HashMap;
+
+#[test]
+fn duplicates_a_page_with_its_fields() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(1, text_field("notes"));
+    let mut form = pdf.into_form();
+
+    assert!(matches!(
+        form.duplicate_page_with_fields(2, "_copy"),
+        Err(ValueError::InvalidPage)
+    ));
+    assert_eq!(form.duplicate_page_with_fields(0, "_copy").unwrap(), 2);
+    assert_eq!(form.len(), 3);
+    form.set_text(2, "copied".to_owned()).unwrap();
+
+    let doc = saved_doc(&mut form);
+    let pages = doc.get_pages();
+    assert_eq!(pages.len(), 3);
+    let copy = doc.get_object(pages[&3]).unwrap().as_dict().unwrap();
+    let annots = copy.get(b"Annots").unwrap().as_array().unwrap();
+    assert_eq!(annots.len(), 1);
+    let widget = doc.get_object(annots[0].as_reference().unwrap()).unwrap();
+    assert_eq!(
+        widget
+            .as_dict()
+            .unwrap()
+            .get(b"T")
+            .unwrap()
+            .as_str()
+            .unwrap(),
+        b"name_copy"
+    );
+    assert_eq!(saved_text(&mut form, "name"), None);
+    assert_eq!(
+        saved_text(&mut form, "name_copy").as_deref(),
+        Some("copied")
+    );
+    assert_eq!(reload(&mut form).len(), 3);
+}
+
+#[test]
+fn detects_linearized_documents() {
+    assert!(!text_form(&["name"]).is_linearized());
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    // lopdf leaves linearization dictionaries out when saving, so the key is renamed in the
+    // output instead, to a name of the same length
+    let mut parameters = Dictionary::new();
+    parameters.set("Linearizex", 1i64);
+    parameters.set("N", 1i64);
+    pdf.doc.add_object(parameters);
+    let mut bytes = pdf.into_bytes();
+    let at = bytes.windows(11).position(|w| w == b"/Linearizex").unwrap();
+    bytes[at + 10] = b'd';
+    assert!(Form::load_from(&bytes[..]).unwrap().is_linearized());
+}
+
+#[test]
+fn splits_a_form_into_one_per_page() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    pdf.add_field(1, text_field("signature"));
+    let mut form = pdf.into_form();
+    form.set_trim_text(true);
+
+    let mut parts: Vec<Form> = form
+        .split_by_page()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].len(), 2);
+    assert_eq!(parts[1].len(), 1);
+    assert_eq!(parts[1].get_full_name(0).as_deref(), Some("signature"));
+    assert_eq!(parts[1].get_field_page(0), Some(0));
+
+    // The options set on the form carry over to its parts
+    parts[1].set_text(0, " Ann ".to_owned()).unwrap();
+    assert_eq!(reload(&mut parts[1]).len(), 1);
+    assert_eq!(
+        saved_text(&mut parts[1], "signature").as_deref(),
+        Some("Ann")
+    );
+    assert_eq!(form.len(), 3);
+}
+
+#[test]
+fn reads_the_open_action() {
+    assert_eq!(text_form(&["name"]).get_open_action(), None);
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let mut action = Dictionary::new();
+    action.set("S", name("JavaScript"));
+    action.set("JS", string("app.alert('hi')"));
+    let action = pdf.doc.add_object(action);
+    pdf.catalog.set("OpenAction", action);
+    assert_eq!(
+        pdf.into_form().get_open_action(),
+        Some(ButtonAction::JavaScript("app.alert('hi')".to_owned()))
+    );
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let page = pdf.page(0);
+    pdf.catalog.set(
+        "OpenAction",
+        Object::Array(vec![Object::Reference(page), name("Fit")]),
+    );
+    assert_eq!(pdf.into_form().get_open_action(), Some(ButtonAction::GoTo));
+}
+
+#[test]
+fn strips_javascript_but_keeps_other_actions() {
+    let mut pdf = TestPdf::new(1);
+    let mut link = Dictionary::new();
+    link.set("S", name("URI"));
+    link.set("URI", string("https://example.com"));
+    let link = pdf.doc.add_object(link);
+    let mut script = Dictionary::new();
+    script.set("S", name("JavaScript"));
+    script.set("JS", string("app.alert(1)"));
+    script.set("Next", link);
+    let script = pdf.doc.add_object(script);
+    let mut events = Dictionary::new();
+    events.set("K", script);
+    events.set("U", link);
+    let mut field = text_field("name");
+    field.set("A", script);
+    field.set("AA", Object::Dictionary(events));
+    pdf.add_field(0, field);
+    let mut scripts = Dictionary::new();
+    scripts.set(
+        "Names",
+        Object::Array(vec![string("init"), Object::Reference(script)]),
+    );
+    let mut names = Dictionary::new();
+    names.set("JavaScript", Object::Dictionary(scripts));
+    pdf.catalog.set("Names", Object::Dictionary(names));
+    let mut form = pdf.into_form();
+
+    form.strip_javascript();
+    assert_eq!(
+        form.get_action(0),
+        Some(ButtonAction::Uri("https://example.com".to_owned()))
+    );
+    let bytes = save(&mut form);
+    assert!(!bytes.windows(10).any(|w| w == b"JavaScript"));
+    let form = Form::load_from(&bytes[..]).unwrap();
+    assert_eq!(
+        form.get_action(0),
+        Some(ButtonAction::Uri("https://example.com".to_owned()))
+    );
+}
+
+#[test]
+fn reads_fields_kept_in_object_streams() {
+    let mut pdf = TestPdf::new(1);
+    let name_id = pdf.add_field(0, text_field("name"));
+    let agree_id = pdf.add_field(0, check_box("agree", "Yes"));
+    // Move the fields into an object stream, which lopdf expands when reading the file
+    let mut offsets = String::new();
+    let mut objects = String::new();
+    for &(id, object) in &[
+        (
+            name_id,
+            "<</FT/Tx/T(name)/V(Ann)/Type/Annot/Subtype/Widget/Rect[10 10 110 30]>>",
+        ),
+        (
+            agree_id,
+            "<</FT/Btn/T(agree)/Type/Annot/Subtype/Widget/Rect[10 40 20 50]>>",
+        ),
+    ] {
+        pdf.doc.objects.remove(&id);
+        offsets.push_str(&format!("{} {} ", id.0, objects.len()));
+        objects.push_str(object);
+        objects.push(' ');
+    }
+    let mut dict = Dictionary::new();
+    // lopdf leaves object streams out when writing, so the type is given once the file is written
+    dict.set("Type", name("XObjStm"));
+    dict.set("N", 2i64);
+    dict.set("First", offsets.len() as i64);
+    let content = format!("{}{}", offsets, objects).into_bytes();
+    pdf.doc.add_object(Stream::new(dict, content));
+    let mut bytes = pdf.into_bytes();
+    let at = bytes.windows(8).position(|w| w == b"/XObjStm").unwrap();
+    bytes[at..at + 8].copy_from_slice(b"/ObjStm ");
+
+    let mut form = Form::load_from(&bytes[..]).unwrap();
+    assert_eq!(form.len(), 2);
+    assert_eq!(form.get_full_name(1).as_deref(), Some("agree"));
+    assert!(matches!(form.get_type(0), FieldType::Text));
+    assert!(matches!(form.get_type(1), FieldType::CheckBox));
+    assert_eq!(saved_text(&mut form, "name").as_deref(), Some("Ann"));
+}
+
+#[test]
+fn saves_compressed_documents_that_reopen() {
+    let mut pdf = TestPdf::new(3);
+    for i in 0..150 {
+        let mut field = text_field(&format!("field{}", i));
+        field.set("V", string("a value that repeats"));
+        pdf.add_field(i % 3, field);
+    }
+    // An object with a generation other than 0 keeps it
+    let marker = (900, 3);
+    pdf.doc
+        .objects
+        .insert(marker, Object::Dictionary(Dictionary::new()));
+    pdf.doc.max_id = 900;
+    let mut form = pdf.into_form();
+    form.set_text(5, "changed (again)".to_owned()).unwrap();
+
+    let plain = save(&mut form);
+    let mut compressed = Vec::new();
+    form.save_compressed_to(&mut compressed).unwrap();
+    assert!(compressed.len() < plain.len());
+    // The binary comment follows the header, marking the file as binary
+    assert_eq!(&compressed[9..15], b"%\xE2\xE3\xCF\xD3\n");
+    // Offsets in a file this small fit in two bytes
+    assert!(compressed.windows(10).any(|w| w == b"/W [1 2 2]"));
+
+    let mut form = Form::load_from(&compressed[..]).unwrap();
+    assert_eq!(form.len(), 150);
+    assert_eq!(
+        saved_text(&mut form, "field5").as_deref(),
+        Some("changed (again)")
+    );
+    assert_eq!(
+        saved_text(&mut form, "field6").as_deref(),
+        Some("a value that repeats")
+    );
+    let doc = Document::load_mem(&compressed).unwrap();
+    assert_eq!(doc.get_pages().len(), 3);
+    assert!(doc.get_object(marker).is_ok());
+}
+
+#[test]
+fn displays_errors_with_their_sources() {
+    use std::error::Error;
+    use std::io;
+
+    let error = Form::load_from(&b"not a pdf"[..]).err().unwrap();
+    assert!(error.to_string().starts_with("could not parse PDF"));
+    assert!(error.source().is_some());
+
+    let error = LoadError::from(io::Error::new(io::ErrorKind::NotFound, "boom"));
+    assert_eq!(error.to_string(), "IO error: boom");
+    assert_eq!(error.source().unwrap().to_string(), "boom");
+    assert_eq!(
+        LoadError::NoSuchReference((4, 0)).to_string(),
+        "reference 4 0 R does not point to any object"
+    );
+
+    assert!(ValueError::TextTooLong.source().is_none());
+    assert_eq!(
+        ValueError::InvalidPage.to_string(),
+        "the page does not exist in the document"
+    );
+}
+
+#[test]
+fn flattens_only_the_given_fields() {
+    let mut pdf = TestPdf::new(1);
+    let mut name = text_field("name");
+    name.set("V", string("Alice"));
+    pdf.add_field(0, name);
+    pdf.add_field(0, text_field("city"));
+    let page = pdf.page(0);
+    let mut form = pdf.into_form();
+
+    form.flatten_fields(&[0]).unwrap();
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("city"));
+    form.set_text(0, "Oslo".to_owned()).unwrap();
+
+    let doc = saved_doc(&mut form);
+    let content = String::from_utf8(doc.get_page_content(page).unwrap()).unwrap();
+    assert!(content.contains("/FlatField0 Do"), "{}", content);
+    let annotations = doc.get_object(page).unwrap().as_dict().unwrap();
+    assert_eq!(
+        annotations
+            .get(b"Annots")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+    let form = reload(&mut form);
+    assert_eq!(form.len(), 1);
+    assert_eq!(text_of(&form, 0), "Oslo");
+}
+
+#[test]
+fn flattens_a_field_nested_in_a_group() {
+    let mut pdf = TestPdf::new(1);
+    let address = pdf.add_group("address");
+    let mut city = text_field("city");
+    city.set("V", string("Oslo"));
+    pdf.add_kid(0, address, city);
+    pdf.add_kid(0, address, text_field("zip"));
+    let mut form = pdf.into_form();
+
+    let city = field_index(&form, "address.city");
+    form.flatten_fields(&[city]).unwrap();
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("address.zip"));
+
+    // The group no longer lists the flattened field, so the output reads back
+    let mut form = reload(&mut form);
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("address.zip"));
+    let group = saved_field(&mut form, "address");
+    assert_eq!(group.get(b"Kids").unwrap().as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn tells_acroform_and_xfa_forms_apart() {
+    assert_eq!(text_form(&["name"]).form_kind(), FormKind::AcroForm);
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.acroform.set("XFA", strings(&["template", "<xdp/>"]));
+    let mut form = pdf.into_form();
+    assert_eq!(form.form_kind(), FormKind::Hybrid);
+    form.set_text(0, "kept".to_owned()).unwrap();
+    assert_eq!(form.form_kind(), FormKind::Hybrid);
+    form.set_clear_xfa_on_change(true);
+    form.set_text(0, "cleared".to_owned()).unwrap();
+    assert_eq!(form.form_kind(), FormKind::AcroForm);
+
+    let mut pdf = TestPdf::new(1);
+    pdf.acroform.set("XFA", strings(&["template", "<xdp/>"]));
+    assert_eq!(pdf.into_form().form_kind(), FormKind::XfaOnly);
+}
+
+#[test]
+fn drops_the_xfa_form() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.acroform.set("XFA", strings(&["template", "<xdp/>"]));
+    let mut form = pdf.into_form();
+
+    form.drop_xfa();
+    assert_eq!(form.form_kind(), FormKind::AcroForm);
+    let mut form = reload(&mut form);
+    assert_eq!(form.form_kind(), FormKind::AcroForm);
+    let doc = saved_doc(&mut form);
+    let acroform = doc
+        .catalog()
+        .unwrap()
+        .get(b"AcroForm")
+        .unwrap()
+        .as_reference()
+        .unwrap();
+    assert!(!doc
+        .get_object(acroform)
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .has(b"XFA"));
+}
+
+#[test]
+fn audits_structural_problems() {
+    let mut pdf = TestPdf::new(1);
+    pdf.acroform.remove(b"DA");
+    pdf.add_field(0, text_field("name"));
+    let mut no_appearance = text_field("city");
+    no_appearance.remove(b"DA");
+    no_appearance.set("Extra", Object::Reference((999, 0)));
+    pdf.add_field(0, no_appearance);
+    let mut no_options = choice("size", 0, &[]);
+    no_options.remove(b"Opt");
+    pdf.add_field(0, no_options);
+    pdf.add_field(0, text_field("name"));
+    let mut check = check_box("agree", "On");
+    check.set("V", name("Yes"));
+    pdf.add_field(0, check);
+    pdf.add_field(0, check_box("subscribe", "Yes"));
+    let form = pdf.into_form();
+
+    let issues = form.audit();
+    let found = |field: usize, text: &str| {
+        issues
+            .iter()
+            .any(|issue| issue.field == field && issue.description.contains(text))
+    };
+    assert!(found(1, "default appearance"), "{:?}", issues);
+    assert!(found(1, "999 0 R"), "{:?}", issues);
+    assert!(found(2, "no options"), "{:?}", issues);
+    assert!(found(3, "field 0"), "{:?}", issues);
+    assert!(found(4, "\"Yes\""), "{:?}", issues);
+    assert_eq!(issues.len(), 5, "{:?}", issues);
+    assert_eq!(issues[0].name.as_deref(), Some("city"));
+}
+
+#[test]
+fn reads_the_document_id() {
+    assert_eq!(text_form(&["name"]).document_id(), None);
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.doc.trailer.set(
+        "ID",
+        Object::Array(vec![
+            Object::String(vec![1, 2, 3], StringFormat::Hexadecimal),
+            Object::String(vec![4, 5], StringFormat::Hexadecimal),
+        ]),
+    );
+    assert_eq!(
+        pdf.into_form().document_id(),
+        Some((vec![1, 2, 3], vec![4, 5]))
+    );
+}
+
+#[test]
+fn regenerates_the_document_id_on_save() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.doc.trailer.set(
+        "ID",
+        Object::Array(vec![
+            Object::String(vec![1, 2, 3], StringFormat::Hexadecimal),
+            Object::String(vec![4, 5], StringFormat::Hexadecimal),
+        ]),
+    );
+    let mut form = pdf.into_form();
+    assert_eq!(
+        reload(&mut form).document_id(),
+        Some((vec![1, 2, 3], vec![4, 5]))
+    );
+
+    form.set_regenerate_id_on_save(true);
+    let (permanent, changing) = reload(&mut form).document_id().unwrap();
+    assert_eq!(permanent, vec![1, 2, 3]);
+    assert_eq!(changing.len(), 16);
+    let (_, again) = reload(&mut form).document_id().unwrap();
+    assert_ne!(again, changing);
+
+    let mut form = text_form(&["name"]);
+    form.set_regenerate_id_on_save(true);
+    let (permanent, changing) = reload(&mut form).document_id().unwrap();
+    assert_eq!((permanent.len(), changing.len()), (16, 16));
+}
+
+#[test]
+fn lists_the_acroform_keys() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.acroform.set("SigFlags", 3i64);
+    let form = pdf.into_form();
+
+    let mut keys = form.acroform_keys();
+    keys.sort();
+    assert_eq!(keys, vec!["DA", "DR", "Fields", "SigFlags"]);
+}
+
+#[test]
+fn loads_an_indirect_field_list() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    let fields = pdf.doc.add_object(Object::Array(pdf.fields.clone()));
+    let mut bytes = pdf.into_bytes();
+    let mut doc = Document::load_mem(&bytes).unwrap();
+    let acroform = doc
+        .catalog()
+        .unwrap()
+        .get(b"AcroForm")
+        .unwrap()
+        .as_reference()
+        .unwrap();
+    doc.get_object_mut(acroform)
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .set("Fields", fields);
+    bytes.clear();
+    doc.save_to(&mut bytes).unwrap();
+
+    let mut form = Form::load_from(&bytes[..]).unwrap();
+    assert_eq!(form.len(), 2);
+    form.duplicate_page_with_fields(0, "_copy").unwrap();
+    assert_eq!(reload(&mut form).len(), 4);
+}
+
+#[test]
+fn rejects_malformed_field_lists() {
+    let mut pdf = TestPdf::new(1);
+    let mut field = text_field("name");
+    field.set("Kids", name("NotAnArray"));
+    pdf.add_field(0, field);
+    assert!(matches!(
+        Form::load_from(&pdf.into_bytes()[..]),
+        Err(LoadError::UnexpectedType)
+    ));
+
+    let mut pdf = TestPdf::new(1);
+    pdf.fields.push(Object::Dictionary(text_field("direct")));
+    assert!(matches!(
+        Form::load_from(&pdf.into_bytes()[..]),
+        Err(LoadError::NotAReference)
+    ));
+
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let mut bytes = pdf.into_bytes();
+    let mut doc = Document::load_mem(&bytes).unwrap();
+    let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_object_mut(root)
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .remove(b"AcroForm");
+    bytes.clear();
+    doc.save_to(&mut bytes).unwrap();
+    assert!(matches!(
+        Form::load_from(&bytes[..]),
+        Err(LoadError::DictionaryKeyNotFound)
+    ));
+}
+
+#[test]
+fn loads_a_catalog_kept_in_the_trailer() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let mut doc = Document::load_mem(&pdf.into_bytes()).unwrap();
+    let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    let catalog = doc.objects.remove(&root).unwrap();
+    doc.trailer.set("Root", catalog);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+
+    let mut form = Form::load_from(&bytes[..]).unwrap();
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_field_page(0), Some(0));
+    form.set_text(0, "Ann".to_owned()).unwrap();
+    assert_eq!(text_of(&reload(&mut form), 0), "Ann");
+}
+
+#[test]
+fn rejects_documents_beyond_the_load_limits() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let address = pdf.add_group("address");
+    pdf.add_kid(0, address, text_field("street"));
+    let bytes = pdf.into_bytes();
+
+    let too_many = Limits {
+        max_fields: 2,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        Form::load_with_limits(&bytes[..], too_many),
+        Err(LoadError::LimitExceeded)
+    ));
+    let too_deep = Limits {
+        max_depth: 1,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        Form::load_with_limits(&bytes[..], too_deep),
+        Err(LoadError::LimitExceeded)
+    ));
+    let too_large = Limits {
+        max_bytes: bytes.len() - 1,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        Form::load_with_limits(&bytes[..], too_large),
+        Err(LoadError::LimitExceeded)
+    ));
+
+    let exact = Limits {
+        max_fields: 3,
+        max_depth: 2,
+        max_bytes: bytes.len(),
+    };
+    assert_eq!(Form::load_with_limits(&bytes[..], exact).unwrap().len(), 2);
+}
+
+#[test]
+fn saves_in_chunks() {
+    struct Chunks {
+        writes: usize,
+        total: usize,
+        largest: usize,
+    }
+
+    impl std::io::Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.total += buf.len();
+            self.largest = self.largest.max(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let titles: Vec<String> = (0..500).map(|i| format!("field{}", i)).collect();
+    let titles: Vec<&str> = titles.iter().map(String::as_str).collect();
+    let mut form = text_form(&titles);
+    let mut chunks = Chunks {
+        writes: 0,
+        total: 0,
+        largest: 0,
+    };
+    form.save_to(&mut chunks).unwrap();
+
+    assert!(chunks.writes > 500);
+    assert!(chunks.largest < chunks.total / 10);
+    assert_eq!(chunks.total, save(&mut form).len());
+}
+
+#[test]
+fn detects_changes_appended_after_signing() {
+    // The signature's byte range has to end where the file does, which its length depends on
+    let signed_file = |length: i64| {
+        let mut pdf = TestPdf::new(1);
+        pdf.add_field(0, text_field("name"));
+        let mut value = Dictionary::new();
+        value.set("Type", name("Sig"));
+        value.set(
+            "ByteRange",
+            Object::Array(vec![
+                Object::Integer(0),
+                Object::Integer(10),
+                Object::Integer(20),
+                Object::Integer(length - 20),
+            ]),
+        );
+        let mut signature = Dictionary::new();
+        signature.set("FT", name("Sig"));
+        signature.set("T", string("signature"));
+        signature.set("V", Object::Dictionary(value));
+        pdf.add_field(0, signature);
+        pdf.into_bytes()
+    };
+    let mut length = 0;
+    let signed = loop {
+        let bytes = signed_file(length);
+        if bytes.len() as i64 == length {
+            break bytes;
+        }
+        length = bytes.len() as i64;
+    };
+    assert!(!Form::load_from(&signed[..])
+        .unwrap()
+        .modified_after_signature());
+
+    let mut updated = signed.clone();
+    let trailer = signed.len() - 40;
+    updated.extend_from_slice(b"\n% appended update\n");
+    updated.extend_from_slice(&signed[trailer..]);
+    assert!(Form::load_from(&updated[..])
+        .unwrap()
+        .modified_after_signature());
+
+    assert!(!text_form(&["name"]).modified_after_signature());
+}
+
+#[test]
+fn strips_annotations_that_are_not_widgets() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    let mut link = Dictionary::new();
+    link.set("Subtype", name("Link"));
+    let link = pdf.doc.add_object(link);
+    pdf.add_annotation(0, Object::Reference(link));
+    let mut note = Dictionary::new();
+    note.set("Subtype", name("Text"));
+    pdf.add_annotation(0, Object::Dictionary(note));
+    let page = pdf.page(0);
+    let mut form = pdf.into_form();
+
+    form.strip_annotations();
+    assert!(form.non_widget_annotations(0).is_empty());
+    let saved = saved_dict(&mut form, page);
+    assert_eq!(saved.get(b"Annots").unwrap().as_array().unwrap().len(), 2);
+    assert_eq!(reload(&mut form).len(), 2);
+}
+
+#[test]
+fn loads_only_the_named_fields() {
+    let mut pdf = TestPdf::new(1);
+    for title in &["a", "b", "c", "d"] {
+        pdf.add_field(0, text_field(title));
+    }
+    let path = std::env::temp_dir().join("pdf_form_load_fields.pdf");
+    std::fs::write(&path, pdf.into_bytes()).unwrap();
+
+    let mut form = Form::load_fields(&path, &["b", "d", "missing"]).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(form.len(), 2);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("b"));
+    assert_eq!(form.get_full_name(1).as_deref(), Some("d"));
+    form.set_text(1, "x".to_owned()).unwrap();
+
+    let form = reload(&mut form);
+    assert_eq!(form.len(), 4);
+    assert_eq!(text_of(&form, 3), "x");
+}
+
+#[test]
+fn loads_only_the_named_fields_of_a_group() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("city"));
+    let address = pdf.add_group("address");
+    pdf.add_kid(0, address, text_field("city"));
+    pdf.add_kid(0, address, text_field("zip"));
+    let path = std::env::temp_dir().join("pdf_form_load_group_fields.pdf");
+    std::fs::write(&path, pdf.into_bytes()).unwrap();
+
+    let form = Form::load_fields(&path, &["address.city", "zip"]).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("address.city"));
+}
+
+#[test]
+fn embeds_the_values_once() {
+    let mut pdf = TestPdf::new(1);
+    let mut full_name = text_field("name");
+    full_name.set("TM", string("applicant_name"));
+    pdf.add_field(0, full_name);
+    pdf.add_field(0, check_box("agree", "Yes"));
+    let mut form = pdf.into_form();
+    form.set_embed_values_on_save(true);
+
+    form.apply_lines("name=Jo\nagree=yes").unwrap();
+    save(&mut form);
+    form.apply_lines("name=Al \"J\"").unwrap();
+    let doc = saved_doc(&mut form);
+
+    let names = doc
+        .catalog()
+        .unwrap()
+        .get(b"Names")
+        .unwrap()
+        .as_dict()
+        .unwrap();
+    let files = names.get(b"EmbeddedFiles").unwrap().as_dict().unwrap();
+    let entries = files.get(b"Names").unwrap().as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].as_str().unwrap(), b"form-data.json");
+    let spec = doc
+        .get_object(entries[1].as_reference().unwrap())
+        .unwrap()
+        .as_dict()
+        .unwrap();
+    let file = spec
+        .get(b"EF")
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get(b"F")
+        .unwrap()
+        .as_reference()
+        .unwrap();
+    let content = &doc.get_object(file).unwrap().as_stream().unwrap().content;
+    assert_eq!(content, br#"{"applicant_name":"Al \"J\"","agree":"Yes"}"#);
+
+    // Nothing a record of a batch embeds is left for the next one
+    let mut records = vec![HashMap::new(), HashMap::new()];
+    records[0].insert("name".to_owned(), FieldValue::Text("Ann".to_owned()));
+    let mut outputs = Vec::new();
+    Form::fill_batch(&form, records, |_, bytes| {
+        outputs.push(bytes);
+        Ok(())
+    })
+    .unwrap();
+    let contains = |bytes: &[u8], text: &[u8]| bytes.windows(text.len()).any(|w| w == text);
+    assert!(contains(&outputs[0], br#""applicant_name":"Ann""#));
+    assert!(!contains(&outputs[1], br#""applicant_name":"Ann""#));
+}
+
+#[test]
+fn sets_the_viewer_preferences() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let mut existing = Dictionary::new();
+    existing.set("Direction", name("R2L"));
+    pdf.catalog
+        .set("ViewerPreferences", Object::Dictionary(existing));
+    let mut form = pdf.into_form();
+    assert_eq!(form.viewer_preferences(), ViewerPreferences::default());
+
+    let mut preferences = form.viewer_preferences();
+    preferences.display_doc_title = true;
+    form.set_viewer_preferences(preferences);
+
+    let doc = saved_doc(&mut form);
+    let saved = doc
+        .catalog()
+        .unwrap()
+        .get(b"ViewerPreferences")
+        .unwrap()
+        .as_dict()
+        .unwrap();
+    assert_eq!(
+        saved.get(b"Direction").unwrap().as_name_str().unwrap(),
+        "R2L"
+    );
+    let preferences = reload(&mut form).viewer_preferences();
+    assert!(preferences.display_doc_title);
+    assert!(!preferences.hide_toolbar);
+    assert!(!preferences.hide_menubar);
+}
@@ -0,0 +1,845 @@
+extern crate lopdf;
+extern crate pdf_form_ids;
+
+mod common;
+
+use common::*;
+use lopdf::{Dictionary, Object, StringFormat};
+use pdf_form_ids::*;
+
+#[test]
+fn counts_filled_fields() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    let mut button = Dictionary::new();
+    button.set("FT", name("Btn"));
+    button.set("Ff", 0x20000i64);
+    button.set("T", string("submit"));
+    pdf.add_field(0, button);
+    let mut form = pdf.into_form();
+    assert_eq!(form.completion(), (0, 3));
+
+    form.set_text(0, "Ann".to_owned()).unwrap();
+    form.set_text(1, String::new()).unwrap();
+    form.set_check_box(2, true).unwrap();
+    assert_eq!(form.completion(), (2, 3));
+}
+
+#[test]
+fn counts_fields_filled_the_way_their_state_reads() {
+    let mut pdf = TestPdf::new(1);
+    // A radio group without a value, whose second button shows its on state
+    let sex = pdf.add_radio(0, "sex", &["0", "1"], Some("1"));
+    pdf.dict_mut(sex).remove(b"V");
+    // A list box selected by its indices alone
+    let mut colors = choice("colors", 0, &["red", "green"]);
+    colors.set("I", Object::Array(vec![Object::Integer(1)]));
+    pdf.add_field(0, colors);
+    // A text field whose value is inherited from its parent
+    let address = pdf.add_group("address");
+    pdf.dict_mut(address).set("V", string("Oslo"));
+    pdf.add_kid(0, address, text_field("city"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.completion(), (3, 4));
+    match form.get_state(0) {
+        FieldState::Radio { selected, .. } => assert_eq!(selected, "1"),
+        _ => panic!("expected a radio group"),
+    }
+}
+
+#[test]
+fn reads_the_field_modification_date() {
+    let mut pdf = TestPdf::new(1);
+    let mut field = text_field("name");
+    field.set("M", string("D:20190101120000Z"));
+    pdf.add_field(0, field);
+    pdf.add_field(0, text_field("city"));
+    let form = pdf.into_form();
+
+    assert_eq!(
+        form.get_field_mod_date(0).as_deref(),
+        Some("D:20190101120000Z")
+    );
+    assert_eq!(form.get_field_mod_date(1), None);
+}
+
+#[test]
+fn reads_the_label_of_a_field_page() {
+    let mut pdf = TestPdf::new(3);
+    for (page, title) in ["cover", "intro", "body"].iter().enumerate() {
+        pdf.add_field(page, text_field(title));
+    }
+    let mut roman = Dictionary::new();
+    roman.set("S", name("r"));
+    let mut appendix = Dictionary::new();
+    appendix.set("S", name("D"));
+    appendix.set("P", string("A-"));
+    let mut labels = Dictionary::new();
+    labels.set(
+        "Nums",
+        Object::Array(vec![
+            Object::Integer(0),
+            Object::Dictionary(roman),
+            Object::Integer(2),
+            Object::Dictionary(appendix),
+        ]),
+    );
+    pdf.catalog.set("PageLabels", Object::Dictionary(labels));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_field_page(1), Some(1));
+    assert_eq!(form.get_field_page_label(0).as_deref(), Some("i"));
+    assert_eq!(form.get_field_page_label(1).as_deref(), Some("ii"));
+    assert_eq!(form.get_field_page_label(2).as_deref(), Some("A-1"));
+    assert_eq!(text_form(&["name"]).get_field_page_label(0), None);
+}
+
+#[test]
+fn reads_and_sets_the_mapping_name() {
+    let mut pdf = TestPdf::new(1);
+    let mut field = text_field("name");
+    field.set("TM", string("customer_name"));
+    pdf.add_field(0, field);
+    pdf.add_field(0, text_field("city"));
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.get_mapping_name(0).as_deref(), Some("customer_name"));
+    assert_eq!(form.get_mapping_name(1), None);
+    form.set_mapping_name(1, "customer_city");
+    let form = reload(&mut form);
+    assert_eq!(form.get_mapping_name(1).as_deref(), Some("customer_city"));
+}
+
+#[test]
+fn detects_rich_text_fields() {
+    let mut pdf = TestPdf::new(1);
+    let mut rich = text_field("notes");
+    rich.set("Ff", 0x2000000i64);
+    pdf.add_field(0, rich);
+    pdf.add_field(0, text_field("name"));
+    let mut check = check_box("agree", "Yes");
+    check.set("Ff", 0x2000000i64);
+    pdf.add_field(0, check);
+    let form = pdf.into_form();
+
+    assert!(form.is_rich_text(0));
+    assert!(!form.is_rich_text(1));
+    assert!(!form.is_rich_text(2));
+}
+
+#[test]
+fn tolerates_loops_in_the_field_tree() {
+    let mut pdf = TestPdf::new(1);
+    let group = pdf.add_group("person");
+    let field = pdf.add_kid(0, group, text_field("name"));
+    // The group is its own kid, and the parent of its parent is its kid
+    pdf.dict_mut(group)
+        .get_mut(b"Kids")
+        .unwrap()
+        .as_array_mut()
+        .unwrap()
+        .push(Object::Reference(group));
+    pdf.dict_mut(group).set("Parent", field);
+    let form = pdf.into_form();
+
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_full_name(0).as_deref(), Some("person.name"));
+}
+
+#[test]
+fn reads_inherited_and_form_quadding() {
+    let mut pdf = TestPdf::new(1);
+    let mut total = text_field("total");
+    total.set("Q", 2i64);
+    pdf.add_field(0, total);
+    let address = pdf.add_group("address");
+    pdf.dict_mut(address).set("Q", 1i64);
+    pdf.add_kid(0, address, text_field("street"));
+    pdf.add_field(0, text_field("name"));
+    let mut form = pdf.into_form();
+    let (total, street, name) = (
+        field_index(&form, "total"),
+        field_index(&form, "address.street"),
+        field_index(&form, "name"),
+    );
+
+    assert_eq!(form.get_quadding(total), Quadding::Right);
+    assert_eq!(form.get_quadding(street), Quadding::Center);
+    assert_eq!(form.get_quadding(name), Quadding::Left);
+    assert_eq!(form.get_form_quadding(), None);
+
+    form.set_form_quadding(Quadding::Right);
+    assert_eq!(form.get_form_quadding(), Some(Quadding::Right));
+    assert_eq!(form.get_quadding(street), Quadding::Center);
+    assert_eq!(form.get_quadding(name), Quadding::Right);
+}
+
+#[test]
+fn indexes_fields_in_authored_order() {
+    let mut pdf = TestPdf::new(1);
+    let address = pdf.add_group("address");
+    pdf.add_kid(0, address, text_field("street"));
+    pdf.add_kid(0, address, text_field("city"));
+    pdf.add_field(0, text_field("phone"));
+    let form = pdf.into_form();
+
+    let names: Vec<String> = (0..form.len())
+        .map(|n| form.get_full_name(n).unwrap())
+        .collect();
+    assert_eq!(names, vec!["address.street", "address.city", "phone"]);
+}
+
+#[test]
+fn reads_and_sets_the_default_style() {
+    let mut pdf = TestPdf::new(1);
+    let mut notes = text_field("notes");
+    notes.set("Ff", 0x2000000i64);
+    notes.set("DS", string("font: 12pt Helvetica"));
+    pdf.add_field(0, notes);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    let mut form = pdf.into_form();
+
+    assert_eq!(
+        form.get_default_style(0).as_deref(),
+        Some("font: 12pt Helvetica")
+    );
+    assert_eq!(form.get_default_style(1), None);
+    form.set_default_style(1, "color: #FF0000").unwrap();
+    assert!(matches!(
+        form.set_default_style(2, "color: #FF0000"),
+        Err(ValueError::TypeMismatch)
+    ));
+    let form = reload(&mut form);
+    assert_eq!(form.get_default_style(1).as_deref(), Some("color: #FF0000"));
+}
+
+#[test]
+fn reads_the_layer_of_a_widget() {
+    let mut pdf = TestPdf::new(1);
+    let mut layer = Dictionary::new();
+    layer.set("Type", name("OCG"));
+    layer.set("Name", string("Annotations"));
+    let layer = pdf.doc.add_object(layer);
+    let mut field = text_field("name");
+    field.set("OC", layer);
+    pdf.add_field(0, field);
+    pdf.add_field(0, text_field("city"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_field_layer(0).as_deref(), Some("Annotations"));
+    assert_eq!(form.get_field_layer(1), None);
+}
+
+#[test]
+fn prefers_option_indices_over_display_text() {
+    let mut pdf = TestPdf::new(1);
+    let mut exported = choice("size", 0, &["s", "m", "l"]);
+    exported.set("V", string("Large"));
+    exported.set("I", Object::Array(vec![Object::Integer(2)]));
+    pdf.add_field(0, exported);
+    let mut stale = choice("color", 0, &["r", "g"]);
+    stale.set("V", string("Red"));
+    stale.set("I", Object::Array(vec![Object::Integer(7)]));
+    pdf.add_field(0, stale);
+    let form = pdf.into_form();
+
+    match form.get_state(0) {
+        FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["l"]),
+        state => panic!("{:?}", state),
+    }
+    match form.get_state(1) {
+        FieldState::ListBox { selected, .. } => assert_eq!(selected, vec!["Red"]),
+        state => panic!("{:?}", state),
+    }
+}
+
+#[test]
+fn lists_the_fields_a_signature_locks() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, text_field("city"));
+    let mut lock = Dictionary::new();
+    lock.set("Type", name("SigFieldLock"));
+    lock.set("Action", name("Include"));
+    lock.set("Fields", strings(&["city", "zip"]));
+    let mut signature = Dictionary::new();
+    signature.set("FT", name("Sig"));
+    signature.set("T", string("signature"));
+    signature.set("Lock", Object::Dictionary(lock));
+    pdf.add_field(0, signature);
+    pdf.add_field(0, text_field("zip"));
+    let mut everything = Dictionary::new();
+    everything.set("Action", name("All"));
+    let mut approval = Dictionary::new();
+    approval.set("FT", name("Sig"));
+    approval.set("T", string("approval"));
+    approval.set("Lock", Object::Dictionary(everything));
+    pdf.add_field(0, approval);
+    let form = pdf.into_form();
+
+    assert_eq!(form.fields_locked_by_signature(2), vec![1, 3]);
+    assert_eq!(form.fields_locked_by_signature(4), vec![0, 1, 2, 3]);
+    assert!(form.fields_locked_by_signature(0).is_empty());
+}
+
+#[test]
+fn reads_the_cells_of_a_comb_field() {
+    let mut pdf = TestPdf::new(1);
+    let mut ssn = text_field("ssn");
+    ssn.set("Ff", 0x1000000i64);
+    ssn.set("MaxLen", 9i64);
+    ssn.set("V", string("12345"));
+    pdf.add_field(0, ssn);
+    pdf.add_field(0, text_field("name"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_comb_cells(0), Some("12345    ".chars().collect()));
+    assert_eq!(form.get_comb_cells(1), None);
+}
+
+#[test]
+fn reads_the_displayed_size_of_a_widget() {
+    let mut pdf = TestPdf::new(1);
+    let mut rotated = text_field("rotated");
+    rotated.set("Rect", rect([10, 10, 40, 110]));
+    let mut characteristics = Dictionary::new();
+    characteristics.set("R", 90i64);
+    rotated.set("MK", Object::Dictionary(characteristics));
+    pdf.add_field(0, rotated);
+    pdf.add_field(0, text_field("upright"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_field_rect(0), Some([10.0, 10.0, 40.0, 110.0]));
+    assert_eq!(form.get_field_display_size(0), Some((100.0, 30.0)));
+    assert_eq!(form.get_field_display_size(1), Some((100.0, 20.0)));
+}
+
+#[test]
+fn finds_values_of_the_wrong_type() {
+    let mut pdf = TestPdf::new(1);
+    let mut named_text = text_field("name");
+    named_text.set("V", name("Yes"));
+    pdf.add_field(0, named_text);
+    let mut text = text_field("city");
+    text.set("V", string("Oslo"));
+    pdf.add_field(0, text);
+    let mut string_check = check_box("agree", "Yes");
+    string_check.set("V", string("Yes"));
+    pdf.add_field(0, string_check);
+    let mut check = check_box("subscribe", "Yes");
+    check.set("V", name("Yes"));
+    pdf.add_field(0, check);
+    pdf.add_field(0, text_field("empty"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.validate_value_types(), vec![0, 2]);
+}
+
+#[test]
+fn counts_distinct_field_names() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(1, text_field("name"));
+    pdf.add_field(1, text_field("city"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.len(), 3);
+    assert_eq!(form.distinct_field_count(), 2);
+}
+
+#[test]
+fn reads_the_commit_on_selection_change_flag() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, choice("size", 0x20000 | 0x8000000, &["S", "M"]));
+    pdf.add_field(0, choice("color", 0x8000000, &["red"]));
+    pdf.add_field(0, choice("shape", 0x20000, &["round"]));
+    let form = pdf.into_form();
+
+    let commits = |n| match form.get_state(n) {
+        FieldState::ComboBox {
+            commit_on_change, ..
+        }
+        | FieldState::ListBox {
+            commit_on_change, ..
+        } => commit_on_change,
+        state => panic!("{:?}", state),
+    };
+    assert!(commits(0));
+    assert!(commits(1));
+    assert!(!commits(2));
+}
+
+#[test]
+fn reads_the_page_media_box() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    let second = pdf.page(1);
+    pdf.dict_mut(second).set("MediaBox", rect([0, 0, 595, 842]));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_page_media_box(0), Some([0.0, 0.0, 612.0, 792.0]));
+    assert_eq!(form.get_page_media_box(1), Some([0.0, 0.0, 595.0, 842.0]));
+    assert_eq!(form.get_page_media_box(2), None);
+}
+
+#[test]
+fn reads_field_rectangles_from_the_top_left() {
+    let mut pdf = TestPdf::new(1);
+    let mut field = text_field("name");
+    field.set("Rect", rect([50, 700, 150, 720]));
+    pdf.add_field(0, field);
+    let form = pdf.into_form();
+
+    assert_eq!(
+        form.get_field_rect_topleft(0),
+        Some([50.0, 72.0, 150.0, 92.0])
+    );
+}
+
+#[test]
+fn reads_the_radio_selection_from_the_widgets() {
+    let mut pdf = TestPdf::new(1);
+    let group = pdf.add_radio(0, "size", &["S", "M", "L"], Some("M"));
+    pdf.dict_mut(group).remove(b"V");
+    pdf.add_radio(0, "color", &["red", "blue"], None);
+    let form = pdf.into_form();
+
+    match form.get_state(0) {
+        FieldState::Radio { selected, options } => {
+            assert_eq!(selected, "M");
+            assert_eq!(options, vec!["S", "M", "L"]);
+        }
+        state => panic!("{:?}", state),
+    }
+    match form.get_state(1) {
+        FieldState::Radio { selected, .. } => assert_eq!(selected, ""),
+        state => panic!("{:?}", state),
+    }
+}
+
+#[test]
+fn finds_the_selected_widget() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_radio(0, "size", &["S", "M", "L"], Some("L"));
+    pdf.add_radio(0, "color", &["red", "blue"], None);
+    pdf.add_field(0, check_box("agree", "Yes"));
+    pdf.add_field(0, text_field("name"));
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.selected_widget(0), Some(2));
+    assert_eq!(form.selected_widget(1), None);
+    assert_eq!(form.selected_widget(2), None);
+    form.set_check_box(2, true).unwrap();
+    assert_eq!(form.selected_widget(2), Some(0));
+    assert_eq!(form.selected_widget(3), None);
+}
+
+#[test]
+fn reads_the_rectangle_from_the_widgets() {
+    let mut pdf = TestPdf::new(1);
+    let group = pdf.add_radio(0, "size", &["S", "M"], None);
+    let kids = pdf
+        .doc
+        .get_object(group)
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get(b"Kids")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone();
+    pdf.dict_mut(kids[0].as_reference().unwrap())
+        .remove(b"Rect");
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_field_rect(0), Some([30.0, 100.0, 40.0, 110.0]));
+}
+
+#[test]
+fn reads_radio_option_pairs() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let sex = pdf.add_radio(0, "sex", &["0", "1"], None);
+    pdf.dict_mut(sex).set("Opt", strings(&["Male", "Female"]));
+    pdf.add_radio(0, "size", &["S", "M"], None);
+    let form = pdf.into_form();
+
+    assert!(form.radio_option_pairs(0).is_empty());
+    assert_eq!(
+        form.radio_option_pairs(1),
+        vec![
+            ("0".to_owned(), "Male".to_owned()),
+            ("1".to_owned(), "Female".to_owned()),
+        ]
+    );
+    assert_eq!(
+        form.radio_option_pairs(2),
+        vec![
+            ("S".to_owned(), "S".to_owned()),
+            ("M".to_owned(), "M".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn checks_whether_fields_share_a_radio_group() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let size = pdf.add_radio(0, "size", &["S", "M"], None);
+    let kids = pdf
+        .doc
+        .get_object(size)
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get(b"Kids")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone();
+    // Kids with a type of their own are loaded as fields too
+    for kid in kids {
+        let widget = pdf.dict_mut(kid.as_reference().unwrap());
+        widget.set("FT", name("Btn"));
+        widget.set("Ff", 0xC000i64);
+    }
+    pdf.add_radio(0, "color", &["red"], None);
+    let form = pdf.into_form();
+
+    // name, size, size's two kids, color
+    assert_eq!(form.len(), 5);
+    assert!(form.same_radio_group(2, 3));
+    assert!(!form.same_radio_group(2, 4));
+    assert!(!form.same_radio_group(0, 0));
+}
+
+#[test]
+fn reads_the_accessible_name() {
+    let mut pdf = TestPdf::new(1);
+    let mut tagged = text_field("tagged");
+    tagged.set("StructParent", 5i64);
+    tagged.set("TU", string("Tooltip"));
+    pdf.add_field(0, tagged);
+    let mut tooltip = text_field("tooltip");
+    tooltip.set("TU", string("Your city"));
+    pdf.add_field(0, tooltip);
+    pdf.add_field(0, text_field("plain"));
+
+    let mut element = Dictionary::new();
+    element.set("S", name("Form"));
+    element.set("Alt", string("Your name"));
+    let element = pdf.doc.add_object(element);
+    let mut leaf = Dictionary::new();
+    leaf.set(
+        "Limits",
+        Object::Array(vec![Object::Integer(5), Object::Integer(5)]),
+    );
+    leaf.set(
+        "Nums",
+        Object::Array(vec![Object::Integer(5), Object::Reference(element)]),
+    );
+    let leaf = pdf.doc.add_object(leaf);
+    let mut parent_tree = Dictionary::new();
+    parent_tree.set("Kids", Object::Array(vec![Object::Reference(leaf)]));
+    let mut tree_root = Dictionary::new();
+    tree_root.set("ParentTree", Object::Dictionary(parent_tree));
+    pdf.catalog
+        .set("StructTreeRoot", Object::Dictionary(tree_root));
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_accessible_name(0).as_deref(), Some("Your name"));
+    assert_eq!(form.get_accessible_name(1).as_deref(), Some("Your city"));
+    assert_eq!(form.get_accessible_name(2), None);
+}
+
+#[test]
+fn counts_the_fields_of_a_type() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    pdf.add_field(0, text_field("city"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.count_of(FieldType::Text), 2);
+    assert_eq!(form.count_of(FieldType::CheckBox), 1);
+    assert_eq!(form.count_of(FieldType::Radio), 0);
+}
+
+#[test]
+fn lists_the_form_resources() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    let logo = pdf.add_stream(b"");
+    {
+        let resources = pdf.acroform.get_mut(b"DR").unwrap().as_dict_mut().unwrap();
+        let mut xobjects = Dictionary::new();
+        xobjects.set("Logo", logo);
+        xobjects.set("Box", logo);
+        resources.set("XObject", Object::Dictionary(xobjects));
+        resources.set("ProcSet", Object::Array(vec![name("PDF"), name("Text")]));
+    }
+    let form = pdf.into_form();
+
+    let resources = form.form_resources();
+    let mut xobjects = resources["XObject"].clone();
+    xobjects.sort();
+    assert_eq!(xobjects, vec!["Box", "Logo"]);
+    assert_eq!(resources["ProcSet"], vec!["PDF", "Text"]);
+    assert_eq!(resources["Font"], vec!["Helv"]);
+}
+
+#[test]
+fn lists_annotations_that_are_not_widgets() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    let mut link = Dictionary::new();
+    link.set("Type", name("Annot"));
+    link.set("Subtype", name("Link"));
+    link.set("Rect", rect([0, 0, 5, 5]));
+    let link = pdf.doc.add_object(link);
+    pdf.add_annotation(0, Object::Reference(link));
+    let mut note = Dictionary::new();
+    note.set("Subtype", name("Text"));
+    pdf.add_annotation(0, Object::Dictionary(note));
+    let form = pdf.into_form();
+
+    assert_eq!(form.non_widget_annotations(0), vec![link]);
+    assert!(form.non_widget_annotations(1).is_empty());
+    assert!(form.non_widget_annotations(5).is_empty());
+}
+
+#[test]
+fn reads_the_page_tab_order() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    let first = pdf.page(0);
+    pdf.dict_mut(first).set("Tabs", name("R"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.page_tabs(0), Some('R'));
+    assert_eq!(form.page_tabs(1), None);
+    assert_eq!(form.page_tabs(7), None);
+}
+
+#[test]
+fn reads_how_values_are_encoded() {
+    let mut pdf = TestPdf::new(1);
+    let mut utf16 = text_field("utf16");
+    utf16.set(
+        "V",
+        Object::String(vec![0xFE, 0xFF, 0, b'h', 0x20, 0xAC], StringFormat::Literal),
+    );
+    pdf.add_field(0, utf16);
+    let mut hex = text_field("hex");
+    hex.set(
+        "V",
+        Object::String(b"hi".to_vec(), StringFormat::Hexadecimal),
+    );
+    pdf.add_field(0, hex);
+    let mut literal = text_field("literal");
+    literal.set("V", string("hi"));
+    pdf.add_field(0, literal);
+    pdf.add_field(0, text_field("empty"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.value_encoding(0), Some(StringEncoding::Utf16Be));
+    assert_eq!(form.value_encoding(1), Some(StringEncoding::Hex));
+    assert_eq!(form.value_encoding(2), Some(StringEncoding::PdfDocEncoding));
+    assert_eq!(form.value_encoding(3), None);
+}
+
+#[test]
+fn finds_the_fields_in_a_region() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("bottom"));
+    let mut top = text_field("top");
+    top.set("Rect", rect([10, 50, 110, 70]));
+    pdf.add_field(0, top);
+    pdf.add_field(1, text_field("next"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.fields_in_region(0, [20.0, 60.0, 20.0, 60.0]), vec![1]);
+    assert_eq!(
+        form.fields_in_region(0, [0.0, 0.0, 200.0, 200.0]),
+        vec![0, 1]
+    );
+    assert_eq!(form.fields_in_region(1, [20.0, 20.0, 20.0, 20.0]), vec![2]);
+    assert!(form
+        .fields_in_region(0, [200.0, 200.0, 300.0, 300.0])
+        .is_empty());
+}
+
+#[test]
+fn reads_the_appearance_state() {
+    let mut pdf = TestPdf::new(1);
+    let mut agree = check_box("agree", "Yes");
+    agree.set("AS", name("Yes"));
+    pdf.add_field(0, agree);
+    pdf.add_field(0, check_box("unchecked", "Yes"));
+    pdf.add_radio(0, "size", &["S", "M"], Some("M"));
+    pdf.add_field(0, text_field("name"));
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.get_appearance_state(0).as_deref(), Some("Yes"));
+    assert_eq!(form.get_appearance_state(1).as_deref(), Some("Off"));
+    assert_eq!(form.get_appearance_state(2).as_deref(), Some("Off"));
+    assert_eq!(form.get_appearance_state(3), None);
+    form.set_check_box(0, false).unwrap();
+    assert_eq!(form.get_appearance_state(0).as_deref(), Some("Off"));
+}
+
+#[test]
+fn resolves_the_selection_from_the_indices() {
+    let mut pdf = TestPdf::new(1);
+    let mut indexed = choice("indexed", 0x200000, &["a", "b", "c"]);
+    indexed.set("V", strings(&["a"]));
+    indexed.set(
+        "I",
+        Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+    );
+    let mut out_of_range = indexed.clone();
+    out_of_range.set("T", string("out_of_range"));
+    out_of_range.set("I", Object::Array(vec![Object::Integer(7)]));
+    pdf.add_field(0, indexed);
+    pdf.add_field(0, out_of_range);
+    pdf.add_field(0, text_field("name"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.resolved_selection(0), vec!["b", "c"]);
+    assert_eq!(form.resolved_selection(1), vec!["a"]);
+    assert!(form.resolved_selection(2).is_empty());
+}
+
+#[test]
+fn counts_the_options() {
+    let mut pdf = TestPdf::new(1);
+    let options: Vec<String> = (0..500).map(|i| i.to_string()).collect();
+    let options: Vec<&str> = options.iter().map(String::as_str).collect();
+    pdf.add_field(0, choice("long", 0x20000, &options));
+    let sizes = pdf.add_group("sizes");
+    pdf.dict_mut(sizes).set("Opt", strings(&["S", "M", "L"]));
+    let mut inherited = choice("size", 0x20000, &[]);
+    inherited.remove(b"Opt");
+    pdf.add_kid(0, sizes, inherited);
+    pdf.add_field(0, text_field("name"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.option_count(0), 500);
+    assert_eq!(form.option_count(1), 3);
+    assert_eq!(form.option_count(2), 0);
+}
+
+#[test]
+fn tells_combo_boxes_from_list_boxes() {
+    let mut pdf = TestPdf::new(1);
+    for &(title, flags, top_index) in &[
+        ("combo_with_top_index", 0x20000, true),
+        ("edit", 0x40000, false),
+        ("edit_with_top_index", 0x40000, true),
+        ("edit_multiselect", 0x240000, false),
+        ("plain", 0, false),
+    ] {
+        let mut field = choice(title, flags, &["a"]);
+        if top_index {
+            field.set("TI", 0i64);
+        }
+        pdf.add_field(0, field);
+    }
+    let form = pdf.into_form();
+
+    assert_eq!(form.get_type(0), FieldType::ComboBox);
+    assert_eq!(form.get_type(1), FieldType::ComboBox);
+    assert_eq!(form.get_type(2), FieldType::ListBox);
+    assert_eq!(form.get_type(3), FieldType::ListBox);
+    assert_eq!(form.get_type(4), FieldType::ListBox);
+}
+
+#[test]
+fn reads_the_default_choice_indices() {
+    let mut pdf = TestPdf::new(1);
+    let mut combo = choice("combo", 0x20000, &[]);
+    combo.set(
+        "Opt",
+        Object::Array(vec![strings(&["x", "Ex"]), string("y"), string("z")]),
+    );
+    combo.set("DV", string("z"));
+    pdf.add_field(0, combo);
+    let mut list = choice("list", 0x200000, &["a", "b", "c"]);
+    list.set("DV", strings(&["c", "nope", "a"]));
+    pdf.add_field(0, list);
+    pdf.add_field(0, choice("none", 0x20000, &["a"]));
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.default_choice_indices(0), vec![2]);
+    assert_eq!(form.default_choice_indices(1), vec![2, 0]);
+    assert!(form.default_choice_indices(2).is_empty());
+    form.set_default_combo_index(0, 0).unwrap();
+    assert_eq!(form.default_choice_indices(0), vec![0]);
+}
+
+#[test]
+fn reads_the_union_of_the_widget_rectangles() {
+    let mut pdf = TestPdf::new(1);
+    let size = pdf.add_radio(0, "size", &["S", "M"], None);
+    let kids = pdf
+        .doc
+        .get_object(size)
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get(b"Kids")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone();
+    pdf.dict_mut(kids[1].as_reference().unwrap())
+        .set("Rect", rect([50, 5, 60, 15]));
+    pdf.add_field(0, text_field("name"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.field_union_rect(0), Some([10.0, 5.0, 60.0, 110.0]));
+    assert_eq!(form.field_union_rect(1), form.get_field_rect(1));
+}
+
+#[test]
+fn reads_the_check_box_export_value() {
+    let mut pdf = TestPdf::new(1);
+    let mut agree = check_box("agree", "Agree");
+    agree.set("V", name("Agree"));
+    agree.set("AS", name("Agree"));
+    pdf.add_field(0, agree);
+    pdf.add_field(0, check_box("unchecked", "Yes"));
+    pdf.add_field(0, text_field("name"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.checkbox_export_value(0).as_deref(), Some("Agree"));
+    assert_eq!(form.checkbox_export_value(1), None);
+    assert_eq!(form.checkbox_export_value(2), None);
+}
+
+#[test]
+fn detects_comb_field_overflow() {
+    let mut pdf = TestPdf::new(1);
+    for &(title, flags, value) in &[
+        ("overflowing", 0x1000000, "1234567"),
+        ("fitting", 0x1000000, "12345"),
+        ("plain", 0, "1234567"),
+    ] {
+        let mut field = text_field(title);
+        field.set("Ff", flags as i64);
+        field.set("MaxLen", 5i64);
+        field.set("V", string(value));
+        pdf.add_field(0, field);
+    }
+    let form = pdf.into_form();
+
+    assert!(form.comb_overflow(0));
+    assert!(!form.comb_overflow(1));
+    assert!(!form.comb_overflow(2));
+}
@@ -0,0 +1,658 @@
+#[cfg(feature = "chrono")]
+extern crate chrono;
+extern crate lopdf;
+extern crate pdf_form_ids;
+
+mod common;
+
+use common::*;
+use lopdf::{Dictionary, Object, StringFormat};
+use pdf_form_ids::*;
+use std::collections::HashMap;
+
+#[test]
+fn sets_the_default_combo_box_option_by_index() {
+    let mut pdf = TestPdf::new(1);
+    let combo = pdf.add_field(0, choice("size", 0x20000, &["S", "M", "L"]));
+    pdf.add_field(0, text_field("name"));
+    let mut form = pdf.into_form();
+
+    form.set_default_combo_index(0, 2).unwrap();
+    assert!(matches!(
+        form.set_default_combo_index(0, 3),
+        Err(ValueError::InvalidSelection)
+    ));
+    assert!(matches!(
+        form.set_default_combo_index(1, 0),
+        Err(ValueError::TypeMismatch)
+    ));
+    let field = saved_dict(&mut form, combo);
+    assert_eq!(field.get(b"DV").unwrap().as_str().unwrap(), b"L");
+}
+
+#[test]
+fn stamps_modified_fields_when_tracking() {
+    let mut form = text_form(&["name", "city"]);
+    form.set_text(0, "Ann".to_owned()).unwrap();
+    assert_eq!(form.get_field_mod_date(0), None);
+
+    form.set_track_field_modifications(true);
+    form.set_text(1, "Oslo".to_owned()).unwrap();
+    assert_eq!(form.get_field_mod_date(0), None);
+    let date = form.get_field_mod_date(1).unwrap();
+    assert!(date.starts_with("D:"), "{}", date);
+    assert!(date[2..16].chars().all(|c| c.is_ascii_digit()), "{}", date);
+}
+
+#[test]
+fn rejects_text_longer_than_a_comb_field() {
+    let mut pdf = TestPdf::new(1);
+    let mut comb = text_field("zip");
+    comb.set("Ff", 0x1000000i64);
+    comb.set("MaxLen", 5i64);
+    pdf.add_field(0, comb);
+    let mut form = pdf.into_form();
+
+    form.set_text(0, "12345".to_owned()).unwrap();
+    assert!(matches!(
+        form.set_text(0, "123456".to_owned()),
+        Err(ValueError::TextTooLong)
+    ));
+    assert_eq!(saved_text(&mut form, "zip").as_deref(), Some("12345"));
+}
+
+#[test]
+fn selects_list_box_options_by_index() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, choice("colors", 0x200000, &["red", "green", "blue"]));
+    pdf.add_field(0, choice("size", 0, &["S", "M"]));
+    let mut form = pdf.into_form();
+
+    form.set_list_box_indices(0, &[2, 0]).unwrap();
+    let colors = saved_field(&mut form, "colors");
+    let selected: Vec<&[u8]> = colors
+        .get(b"V")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str().unwrap())
+        .collect();
+    assert_eq!(selected, vec![&b"red"[..], b"blue"]);
+    let indices: Vec<i64> = colors
+        .get(b"I")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|index| index.as_i64().unwrap())
+        .collect();
+    assert_eq!(indices, vec![0, 2]);
+    assert!(matches!(
+        form.set_list_box_indices(0, &[3]),
+        Err(ValueError::InvalidSelection)
+    ));
+    assert!(matches!(
+        form.set_list_box_indices(1, &[0, 1]),
+        Err(ValueError::TooManySelected)
+    ));
+    // An index given twice is a single selection
+    form.set_list_box_indices(1, &[1, 1]).unwrap();
+    assert_eq!(saved_text(&mut form, "size").as_deref(), Some("M"));
+}
+
+#[test]
+fn groups_check_boxes_as_a_radio_group() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, check_box("small", "Yes"));
+    pdf.add_field(0, check_box("large", "Yes"));
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("medium", "Yes"));
+    let mut form = pdf.into_form();
+    form.set_check_box(1, true).unwrap();
+
+    assert!(matches!(
+        form.group_as_radio(&[0, 2], "size"),
+        Err(ValueError::TypeMismatch)
+    ));
+    assert_eq!(form.group_as_radio(&[0, 1, 3], "size").unwrap(), 1);
+    assert_eq!(form.len(), 2);
+    assert!(matches!(form.get_type(1), FieldType::Radio));
+
+    let mut form = reload(&mut form);
+    assert_eq!(form.len(), 2);
+    assert_eq!(form.get_full_name(1).as_deref(), Some("size"));
+    let size = saved_field(&mut form, "size");
+    let doc = saved_doc(&mut form);
+    let states: Vec<&[u8]> = size
+        .get(b"Kids")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|kid| {
+            let widget = doc.get_object(kid.as_reference().unwrap()).unwrap();
+            widget
+                .as_dict()
+                .unwrap()
+                .get(b"AS")
+                .unwrap()
+                .as_name()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!((states[0], states[2]), (&b"Off"[..], &b"Off"[..]));
+    assert_ne!(states[1], b"Off");
+}
+
+#[test]
+fn fills_a_separate_document_per_record() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    let template = pdf.into_form();
+
+    let mut records = Vec::new();
+    for name in &["Alice Smith", "Bob Jones", ""] {
+        let mut record = HashMap::new();
+        if !name.is_empty() {
+            record.insert("name".to_owned(), FieldValue::Text(name.to_string()));
+        }
+        if *name == "Alice Smith" {
+            record.insert("agree".to_owned(), FieldValue::CheckBox(true));
+        }
+        records.push(record);
+    }
+    let mut outputs = Vec::new();
+    Form::fill_batch(&template, records, |i, bytes| {
+        outputs.push((i, bytes));
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(
+        outputs.iter().map(|&(i, _)| i).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    let mut forms: Vec<Form> = outputs
+        .iter()
+        .map(|(_, bytes)| Form::load_from(&bytes[..]).unwrap())
+        .collect();
+    assert_eq!(
+        saved_text(&mut forms[0], "name").as_deref(),
+        Some("Alice Smith")
+    );
+    // The box is checked with the on state its appearance has
+    let agree = saved_field(&mut forms[0], "agree");
+    assert_eq!(agree.get(b"V").unwrap().as_name().unwrap(), b"Yes");
+    assert_eq!(agree.get(b"AS").unwrap().as_name().unwrap(), b"Yes");
+    assert_eq!(
+        saved_text(&mut forms[1], "name").as_deref(),
+        Some("Bob Jones")
+    );
+    assert!(!saved_field(&mut forms[1], "agree").has(b"V"));
+    assert_eq!(forms[2].completion(), (0, 2));
+    // Nothing one record set is left for the next
+    let contains = |bytes: &[u8], text: &[u8]| bytes.windows(text.len()).any(|w| w == text);
+    assert!(contains(&outputs[0].1, b"Alice Smith"));
+    assert!(!contains(&outputs[1].1, b"Alice Smith"));
+    assert!(!contains(&outputs[2].1, b"Bob Jones"));
+    assert_eq!(template.completion(), (0, 2));
+}
+
+#[test]
+fn stops_a_batch_at_the_first_record_that_fails() {
+    let template = text_form(&["name"]);
+    let mut records = vec![HashMap::new(), HashMap::new(), HashMap::new()];
+    records[0].insert("name".to_owned(), FieldValue::Text("Ann".to_owned()));
+    records[1].insert("name".to_owned(), FieldValue::CheckBox(true));
+    records[2].insert("name".to_owned(), FieldValue::Text("Bob".to_owned()));
+    let mut outputs = Vec::new();
+    let error = Form::fill_batch(&template, records, |i, _| {
+        outputs.push(i);
+        Ok(())
+    })
+    .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(outputs, vec![0]);
+}
+
+#[test]
+fn switches_choice_fields_between_combo_and_list_box() {
+    let mut pdf = TestPdf::new(1);
+    let mut size = choice("size", 0x60000, &["S", "M"]);
+    size.set("V", string("M"));
+    pdf.add_field(0, size);
+    pdf.add_field(0, text_field("name"));
+    let mut form = pdf.into_form();
+    assert!(matches!(form.get_type(0), FieldType::ComboBox));
+
+    form.set_choice_style(0, false).unwrap();
+    assert!(matches!(form.get_type(0), FieldType::ListBox));
+    form.set_choice_style(0, true).unwrap();
+    let size = saved_field(&mut form, "size");
+    assert_eq!(size.get(b"V").unwrap().as_str().unwrap(), b"M");
+    assert_ne!(size.get(b"Ff").unwrap().as_i64().unwrap() & 0x20000, 0);
+    assert!(matches!(form.get_type(0), FieldType::ComboBox));
+    assert!(matches!(
+        form.set_choice_style(1, true),
+        Err(ValueError::TypeMismatch)
+    ));
+}
+
+#[test]
+fn links_a_field_to_a_uri() {
+    let mut form = text_form(&["help"]);
+    assert_eq!(form.get_action(0), None);
+
+    form.set_field_link(0, "https://example.com/help");
+    let form = reload(&mut form);
+    assert_eq!(
+        form.get_action(0),
+        Some(ButtonAction::Uri("https://example.com/help".to_owned()))
+    );
+}
+
+#[test]
+fn sets_every_field_of_a_name() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(1, text_field("name"));
+    pdf.add_field(1, text_field("city"));
+    let mut form = pdf.into_form();
+
+    form.set_group_value("name", FieldValue::Text("Ann".to_owned()))
+        .unwrap();
+    assert_eq!(text_of(&form, 0), "Ann");
+    assert_eq!(text_of(&form, 1), "Ann");
+    assert_eq!(text_of(&form, 2), "");
+    assert!(matches!(
+        form.set_group_value("zip", FieldValue::Text("0150".to_owned())),
+        Err(ValueError::NoSuchField)
+    ));
+    assert!(matches!(
+        form.set_group_value("name", FieldValue::CheckBox(true)),
+        Err(ValueError::TypeMismatch)
+    ));
+}
+
+#[test]
+fn coerces_values_of_the_wrong_type() {
+    let mut pdf = TestPdf::new(1);
+    let mut named_text = text_field("name");
+    named_text.set("V", name("Ann"));
+    pdf.add_field(0, named_text);
+    let mut string_check = check_box("agree", "Yes");
+    string_check.set("V", string("Yes"));
+    pdf.add_field(0, string_check);
+    let mut broken = text_field("city");
+    broken.set("V", Object::Dictionary(Dictionary::new()));
+    pdf.add_field(0, broken);
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.coerce_value_types(), vec![2]);
+    assert_eq!(form.validate_value_types(), vec![2]);
+    assert_eq!(text_of(&form, 0), "Ann");
+    assert!(is_checked(&form, 1));
+}
+
+#[test]
+fn makes_a_combo_box_editable() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, choice("size", 0x20000, &["S", "M"]));
+    pdf.add_field(0, choice("color", 0, &["red"]));
+    let mut form = pdf.into_form();
+
+    assert!(matches!(
+        form.set_combo_box(0, "XL".to_owned()),
+        Err(ValueError::InvalidSelection)
+    ));
+    form.set_combo_editable(0, true).unwrap();
+    form.set_combo_box(0, "XL".to_owned()).unwrap();
+    match form.get_state(0) {
+        FieldState::ComboBox {
+            selected, editable, ..
+        } => {
+            assert_eq!(selected, vec!["XL"]);
+            assert!(editable);
+        }
+        state => panic!("{:?}", state),
+    }
+    form.set_combo_editable(0, false).unwrap();
+    assert!(matches!(form.get_type(0), FieldType::ComboBox));
+    assert!(matches!(
+        form.set_combo_editable(1, true),
+        Err(ValueError::TypeMismatch)
+    ));
+}
+
+#[test]
+fn exports_a_csv_row() {
+    let mut pdf = TestPdf::new(1);
+    let mut full_name = text_field("name");
+    full_name.set("V", string("Doe, \"J\""));
+    pdf.add_field(0, full_name);
+    let mut agree = check_box("agree", "Yes");
+    agree.set("V", name("Yes"));
+    pdf.add_field(0, agree);
+    pdf.add_field(0, text_field("city"));
+    let form = pdf.into_form();
+
+    assert_eq!(form.csv_headers(), vec!["name", "agree", "city"]);
+    assert_eq!(
+        form.export_csv_row(&["agree", "name", "missing", "city"]),
+        "Yes,\"Doe, \"\"J\"\"\",,"
+    );
+}
+
+#[test]
+fn exports_fields_under_their_mapping_names() {
+    let mut pdf = TestPdf::new(1);
+    let mut full_name = text_field("name");
+    full_name.set("TM", string("applicant_name"));
+    full_name.set("V", string("Jo"));
+    pdf.add_field(0, full_name);
+    pdf.add_field(0, text_field("city"));
+    let mut form = pdf.into_form();
+
+    assert_eq!(form.csv_headers(), vec!["applicant_name", "city"]);
+    assert_eq!(form.export_csv_row(&["applicant_name", "name"]), "Jo,");
+
+    form.apply_csv_row(&["applicant_name", "city"], "Al,Oslo")
+        .unwrap();
+    assert_eq!(text_of(&form, 0), "Al");
+    assert_eq!(text_of(&form, 1), "Oslo");
+}
+
+#[test]
+fn imports_a_csv_row() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("agree", "Yes"));
+    pdf.add_field(0, text_field("city"));
+    let mut form = pdf.into_form();
+
+    form.apply_csv_row(
+        &["name", "agree", "city", "unknown"],
+        "\"Doe, \"\"J\"\"\",Yes,Oslo,x\r\n",
+    )
+    .unwrap();
+    assert_eq!(text_of(&form, 0), "Doe, \"J\"");
+    assert!(is_checked(&form, 1));
+    assert_eq!(text_of(&form, 2), "Oslo");
+    // The box is checked with the on state of its appearance rather than "On"
+    let agree = saved_field(&mut form, "agree");
+    assert_eq!(agree.get(b"V").unwrap().as_name().unwrap(), b"Yes");
+    assert_eq!(agree.get(b"AS").unwrap().as_name().unwrap(), b"Yes");
+}
+
+#[test]
+fn selects_a_radio_button_by_its_label() {
+    let mut pdf = TestPdf::new(1);
+    let sex = pdf.add_radio(0, "sex", &["0", "1"], Some("0"));
+    pdf.dict_mut(sex).set("Opt", strings(&["Male", "Female"]));
+    let mut form = pdf.into_form();
+
+    form.set_radio_by_label(0, "Female").unwrap();
+    match form.get_state(0) {
+        FieldState::Radio { selected, .. } => assert_eq!(selected, "1"),
+        state => panic!("{:?}", state),
+    }
+    assert_eq!(form.selected_widget(0), Some(1));
+    assert!(matches!(
+        form.set_radio_by_label(0, "Other"),
+        Err(ValueError::InvalidSelection)
+    ));
+
+    let doc = saved_doc(&mut form);
+    let kids = doc
+        .get_object(sex)
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .get(b"Kids")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .clone();
+    let states: Vec<_> = kids
+        .iter()
+        .map(|kid| {
+            let widget = doc
+                .get_object(kid.as_reference().unwrap())
+                .unwrap()
+                .as_dict()
+                .unwrap();
+            widget.get(b"AS").unwrap().as_name_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(states, vec!["Off", "1"]);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn reads_a_date_from_the_format_script() {
+    use chrono::NaiveDate;
+
+    let mut pdf = TestPdf::new(1);
+    for &(title, script) in &[
+        ("custom", "AFDate_FormatEx(\"dd-mmm-yyyy\");"),
+        ("builtin", "AFDate_Format(2);"),
+        ("plain", ""),
+    ] {
+        let mut field = text_field(title);
+        if !script.is_empty() {
+            let mut action = Dictionary::new();
+            action.set("S", name("JavaScript"));
+            action.set("JS", string(script));
+            let mut actions = Dictionary::new();
+            actions.set("F", Object::Dictionary(action));
+            field.set("AA", Object::Dictionary(actions));
+        }
+        pdf.add_field(0, field);
+    }
+    let mut form = pdf.into_form();
+
+    form.set_text(0, "05-Mar-2021".to_owned()).unwrap();
+    form.set_text(1, "12/31/99".to_owned()).unwrap();
+    form.set_text(2, "12/31/99".to_owned()).unwrap();
+    assert_eq!(form.get_date(0), NaiveDate::from_ymd_opt(2021, 3, 5));
+    assert_eq!(form.get_date(1), NaiveDate::from_ymd_opt(1999, 12, 31));
+    assert_eq!(form.get_date(2), None);
+    form.set_text(1, "13/31/99".to_owned()).unwrap();
+    assert_eq!(form.get_date(1), None);
+}
+
+#[test]
+fn checks_values_against_the_keystroke_script() {
+    let mut pdf = TestPdf::new(1);
+    for &(title, script) in &[
+        ("amount", "AFNumber_Keystroke(2, 0, 0, 0, \"$\", true);"),
+        ("phone", "AFSpecial_Keystroke(2);"),
+    ] {
+        let mut action = Dictionary::new();
+        action.set("S", name("JavaScript"));
+        action.set("JS", string(script));
+        let mut actions = Dictionary::new();
+        actions.set("K", Object::Dictionary(action));
+        let mut field = text_field(title);
+        field.set("AA", Object::Dictionary(actions));
+        pdf.add_field(0, field);
+    }
+    pdf.add_field(0, text_field("notes"));
+    let form = pdf.into_form();
+
+    assert!(form.value_matches_format(0, "1234"));
+    assert!(form.value_matches_format(0, "$1,234.50"));
+    assert!(form.value_matches_format(0, "-12"));
+    assert!(!form.value_matches_format(0, "12a"));
+    assert!(form.value_matches_format(1, "(555) 123-4567"));
+    assert!(!form.value_matches_format(1, "555-12"));
+    assert!(form.value_matches_format(2, "anything"));
+}
+
+#[test]
+fn fills_from_name_value_lines() {
+    let mut pdf = TestPdf::new(1);
+    pdf.add_field(0, text_field("name"));
+    pdf.add_field(0, check_box("agree", "Agreed"));
+    pdf.add_field(0, text_field("city"));
+    let mut form = pdf.into_form();
+
+    form.apply_lines(
+        "# a comment\nname = Jane Doe\n\nagree=yes\r\nnobody=x\nno equals\ncity=a=b\n",
+    )
+    .unwrap();
+    assert_eq!(text_of(&form, 0), "Jane Doe");
+    assert!(is_checked(&form, 1));
+    assert_eq!(text_of(&form, 2), "a=b");
+    let agree = saved_field(&mut form, "agree");
+    assert_eq!(agree.get(b"V").unwrap().as_name().unwrap(), b"Agreed");
+    assert_eq!(agree.get(b"AS").unwrap().as_name().unwrap(), b"Agreed");
+}
+
+#[test]
+fn normalizes_value_encodings() {
+    let mut pdf = TestPdf::new(1);
+    let mut utf8 = text_field("utf8");
+    utf8.set(
+        "V",
+        Object::String("Zoë €5".as_bytes().to_vec(), StringFormat::Literal),
+    );
+    pdf.add_field(0, utf8);
+    let mut utf16 = text_field("utf16");
+    utf16.set(
+        "V",
+        Object::String(
+            vec![0xFE, 0xFF, 0, b'h', 0, b'i'],
+            StringFormat::Hexadecimal,
+        ),
+    );
+    pdf.add_field(0, utf16);
+    let mut latin1 = text_field("latin1");
+    latin1.set("V", Object::String(vec![b'c', 0xE9], StringFormat::Literal));
+    pdf.add_field(0, latin1);
+    let mut form = pdf.into_form();
+
+    form.normalize_encoding();
+    assert_eq!(text_of(&form, 0), "Zoë €5");
+    assert_eq!(form.value_encoding(0), Some(StringEncoding::Utf16Be));
+    assert_eq!(text_of(&form, 1), "hi");
+    assert_eq!(form.value_encoding(1), Some(StringEncoding::PdfDocEncoding));
+    assert_eq!(text_of(&form, 2), "cé");
+}
+
+#[test]
+fn sets_text_outside_ascii() {
+    let mut form = text_form(&["latin", "japanese"]);
+
+    form.set_text(0, "Zoë".to_owned()).unwrap();
+    form.set_text(1, "日本".to_owned()).unwrap();
+    assert_eq!(form.value_encoding(0), Some(StringEncoding::PdfDocEncoding));
+    assert_eq!(form.value_encoding(1), Some(StringEncoding::Utf16Be));
+    let reloaded = reload(&mut form);
+    assert_eq!(text_of(&reloaded, 0), "Zoë");
+    assert_eq!(text_of(&reloaded, 1), "日本");
+}
+
+#[test]
+fn duplicates_a_field() {
+    let mut form = text_form(&["name"]);
+
+    let copy = form
+        .duplicate_field(0, "name2", [10.0, 40.0, 110.0, 60.0])
+        .unwrap();
+    assert_eq!(copy, 1);
+    form.set_text(0, "one".to_owned()).unwrap();
+    form.set_text(copy, "two".to_owned()).unwrap();
+
+    let form = reload(&mut form);
+    assert_eq!(form.len(), 2);
+    let copy = field_index(&form, "name2");
+    assert_eq!(text_of(&form, field_index(&form, "name")), "one");
+    assert_eq!(text_of(&form, copy), "two");
+    assert_eq!(form.get_field_rect(copy), Some([10.0, 40.0, 110.0, 60.0]));
+    assert_eq!(form.get_field_page(copy), Some(0));
+}
+
+#[test]
+fn sets_the_appearance_state() {
+    let mut pdf = TestPdf::new(1);
+    let agree = pdf.add_field(0, check_box("agree", "Yes"));
+    let only_on = pdf.add_field(0, check_box("only_on", "Yes"));
+    pdf.dict_mut(only_on)
+        .get_mut(b"AP")
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .get_mut(b"N")
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .remove(b"Off");
+    let mut form = pdf.into_form();
+
+    form.set_appearance_state(0, "Yes").unwrap();
+    assert_eq!(form.get_appearance_state(0).as_deref(), Some("Yes"));
+    form.set_appearance_state(0, "Off").unwrap();
+    assert_eq!(form.get_appearance_state(0).as_deref(), Some("Off"));
+    assert!(matches!(
+        form.set_appearance_state(0, "On"),
+        Err(ValueError::InvalidSelection)
+    ));
+    form.set_appearance_state(1, "Yes").unwrap();
+    assert!(form.set_appearance_state(1, "Off").is_err());
+    assert_eq!(
+        saved_dict(&mut form, agree)
+            .get(b"AS")
+            .unwrap()
+            .as_name_str()
+            .unwrap(),
+        "Off"
+    );
+}
+
+#[test]
+fn hashes_values_regardless_of_field_order() {
+    let mut first = text_form(&["x", "y"]);
+    let mut second = text_form(&["y", "x"]);
+    first.set_text(0, "1".to_owned()).unwrap();
+    second.set_text(1, "1".to_owned()).unwrap();
+    assert_eq!(first.values_hash(), second.values_hash());
+
+    second.set_text(1, "2".to_owned()).unwrap();
+    assert_ne!(first.values_hash(), second.values_hash());
+}
+
+#[test]
+fn duplicates_a_field_without_a_page_reference() {
+    let mut pdf = TestPdf::new(2);
+    pdf.add_field(0, text_field("first"));
+    let second = pdf.add_field(1, text_field("second"));
+    // The page is then only known from the page's annotations
+    pdf.dict_mut(second).remove(b"P");
+    let mut form = pdf.into_form();
+
+    let copy = form
+        .duplicate_field(1, "copy", [0.0, 0.0, 50.0, 20.0])
+        .unwrap();
+    assert_eq!(form.get_field_page(copy), Some(1));
+    let form = reload(&mut form);
+    let copy = field_index(&form, "copy");
+    assert_eq!(form.get_field_page(copy), Some(1));
+    assert!(form
+        .fields_in_region(1, [10.0, 10.0, 10.0, 10.0])
+        .contains(&copy));
+}
+
+#[test]
+fn trims_text_when_asked() {
+    let mut form = text_form(&["untrimmed", "trimmed"]);
+
+    form.set_text(0, "  padded \n".to_owned()).unwrap();
+    form.set_trim_text(true);
+    form.set_text(1, "  line one\n  line two  \n".to_owned())
+        .unwrap();
+    assert_eq!(text_of(&form, 0), "  padded \n");
+    assert_eq!(text_of(&form, 1), "line one\n  line two");
+}